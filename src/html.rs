@@ -79,16 +79,92 @@ li a:hover{{text-decoration:underline}}
 {}</body></html>"#, dark_css, private_link, settings_link, main_content)
 }
 
-pub fn settings(cleared: bool, engine: &str, custom_url: &str, dark: bool, cache_enabled: bool, cache_cleared: bool, cookies_cleared: bool) -> String {
+pub struct SettingsPage<'a> {
+    pub engine: &'a str,
+    pub custom_url: &'a str,
+    pub dark: bool,
+    pub cache_enabled: bool,
+    pub https_only: bool,
+    pub https_exceptions: &'a [String],
+    pub tls_exceptions: &'a [String],
+    pub allowed_schemes: &'a [String],
+    pub cookie_policy: &'a str,
+    pub itp: bool,
+    pub cookie_exceptions: &'a [(String, bool)],
+    pub proxy_mode: &'a str,
+    pub proxy_http: &'a str,
+    pub proxy_https: &'a str,
+    pub proxy_socks: &'a str,
+    pub proxy_ignore: &'a str,
+    pub proxy_pac: &'a str,
+    pub gpc: bool,
+    pub dnt: bool,
+    pub user_agent: &'a str,
+    pub site_user_agents: &'a [(String, String)],
+    pub strip_tracking: bool,
+    pub tracking_params: &'a str,
+    pub site_tracking_params: &'a [(String, String)],
+    pub containers: &'a [(String, String)],
+    pub container_sites: &'a [(String, String)],
+    pub vertical_tabs: bool,
+    pub discard_after_minutes: u32,
+    pub discard_on_low_memory: bool,
+    pub confirm_close: bool,
+    pub confirm_close_tabs: u32,
+    pub cleared: bool,
+    pub cache_cleared: bool,
+    pub cookies_cleared: bool,
+    pub nonce: &'a str,
+}
+
+pub fn settings(page: &SettingsPage) -> String {
+    let SettingsPage { engine, custom_url, dark, cache_enabled, cleared, cache_cleared, cookies_cleared, nonce, .. } = *page;
     let msg = if cleared { "<p class=\"msg\">History cleared.</p>" } else { "" };
     let cache_msg = if cache_cleared { "<p class=\"msg\">Cache cleared.</p>" } else { "" };
     let cookies_msg = if cookies_cleared { "<p class=\"msg\">Cookies cleared. Takes effect after restart.</p>" } else { "" };
+    let exceptions = host_section("HTTP Exceptions", page.https_exceptions, "remove_https_exception", "", nonce)
+        + &host_section("Certificate Exceptions", page.tls_exceptions, "remove_tls_exception", "Removals take effect on next launch.", nonce)
+        + &host_section("External Links", page.allowed_schemes, "remove_allowed_scheme", "These link types open their app without asking.", nonce);
+    let proxy_chk = |m: &str| if page.proxy_mode == m { " checked" } else { "" };
+    let cookie_chk = |p: &str| if page.cookie_policy == p { " checked" } else { "" };
+    let cookie_rules = if page.cookie_exceptions.is_empty() { String::new() } else {
+        let rows: String = page.cookie_exceptions.iter().map(|(h, allow)| format!(
+            "<li><span>{} <em>{}</em></span><a href=\"rug://settings?nonce={}&amp;remove_cookie_exception={}\">Remove</a></li>",
+            esc(h), if *allow { "allowed" } else { "blocked" }, esc(nonce), esc(h)
+        )).collect();
+        format!("<ul class=\"hosts rules\">{}</ul>\n  ", rows)
+    };
+    let site_user_agents = if page.site_user_agents.is_empty() { String::new() } else {
+        let rows: String = page.site_user_agents.iter().map(|(h, preset)| format!(
+            "<li><span>{} <em>{}</em></span><a href=\"rug://settings?nonce={}&amp;remove_ua_site={}\">Remove</a></li>",
+            esc(h), user_agent_name(preset), esc(nonce), esc(h)
+        )).collect();
+        format!("<ul class=\"hosts\">{}</ul>\n  ", rows)
+    };
+    let site_tracking_params = if page.site_tracking_params.is_empty() { String::new() } else {
+        let rows: String = page.site_tracking_params.iter().map(|(h, names)| format!(
+            "<li><span>{} <em>{}</em></span><a href=\"rug://settings?nonce={}&amp;remove_tracking_site={}\">Remove</a></li>",
+            esc(h), esc(names), esc(nonce), esc(h)
+        )).collect();
+        format!("<ul class=\"hosts rules\">{}</ul>\n  ", rows)
+    };
+    let containers: String = page.containers.iter().map(|(name, color)| format!(
+        "<li><span><i class=\"swatch\" style=\"background:{}\"></i>{}</span><a href=\"rug://settings?nonce={}&amp;remove_container={}\">Remove</a></li>",
+        esc(color), esc(name), esc(nonce), esc(&query_escape(name))
+    )).collect();
+    let container_sites: String = page.container_sites.iter().map(|(h, name)| format!(
+        "<li><span>{} <em>{}</em></span><a href=\"rug://settings?nonce={}&amp;remove_container_site={}\">Remove</a></li>",
+        esc(h), esc(name), esc(nonce), esc(h)
+    )).collect();
+    let container_options: String = page.containers.iter()
+        .map(|(name, _)| format!("<option value=\"{}\">{}</option>", esc(name), esc(name)))
+        .collect();
     let chk = |e: &str| if engine == e { " checked" } else { "" };
     let custom_display = if engine == "custom" { "block" } else { "none" };
     let dark_css = if dark { r"
 body{background:#18181b;color:#e4e4e7}
 h2{color:#52525b}
-input[type=text]{background:#27272a;color:#e4e4e7;border-color:#3f3f46}
+input[type=text],.site-rule select{background:#27272a;color:#e4e4e7;border-color:#3f3f46}
 button,.btn{background:#27272a;color:#e4e4e7}
 button:hover,.btn:hover{background:#3f3f46}
 button.dirty{background:#3b82f6;color:#fff}
@@ -96,7 +172,7 @@ button.dirty:hover{background:#2563eb}
 .home{color:#52525b}
 .home:hover{color:#e4e4e7}
 " } else { "" };
-    format!(r#"<!DOCTYPE html>
+    format!(r##"<!DOCTYPE html>
 <html lang="en">
 <head><meta charset="UTF-8"><title>settings</title><style>
 body{{margin:0;font-family:Arial,sans-serif;display:flex;flex-direction:column;align-items:center;padding-top:10vh;background:#fff}}
@@ -115,12 +191,24 @@ button.dirty{{background:#3b82f6;color:#fff;opacity:1}}
 button.dirty:hover{{background:#2563eb}}
 .msg{{color:green;margin:0 0 10px;font-size:.9em}}
 .note{{font-size:.8em;color:#999;margin:4px 0 0}}
+.hosts{{list-style:none;padding:0;margin:0;font-size:.9em}}
+.hosts li{{display:flex;justify-content:space-between;gap:16px;padding:3px 0}}
+.hosts a{{color:#999;font-size:.85em}}
+.hosts em{{color:#999;font-size:.85em}}
+.rules{{margin-top:10px}}
+.site-rule{{display:flex;gap:6px;margin-top:10px}}
+.site-rule input[type=text]{{margin-top:0}}
+.site-rule button{{padding:6px 10px}}
+.site-rule select{{border:1px solid #ccc;border-radius:4px;font-size:.9em;background:#fff}}
+.site-rule input[type=color]{{width:36px;padding:0;border:none;background:none}}
+.swatch{{display:inline-block;width:10px;height:10px;border-radius:50%;margin-right:8px}}
 .home{{position:fixed;top:16px;right:24px;color:#aaa;font-size:.875em;text-decoration:none}}
 .home:hover{{color:#333}}
 {}</style></head>
 <body><a class="home" href="rug://home">Home</a><h1>settings</h1>
 <div class="cols">
 <form id="settings-form" method="get" action="rug://settings" class="col">
+<input type="hidden" name="nonce" value="{nonce}">
 <div class="section">
   <h2>Search</h2>
   <label><input type="radio" name="engine" value="ddg"{}> DuckDuckGo</label>
@@ -136,25 +224,155 @@ button.dirty:hover{{background:#2563eb}}
   <label><input type="radio" name="theme" value="light"{}> Light</label>
   <label><input type="radio" name="theme" value="dark"{}> Dark</label>
 </div>
+<div class="section">
+  <h2>Tabs</h2>
+  <label><input type="radio" name="tab_layout" value="horizontal"{}> Across the top</label>
+  <label><input type="radio" name="tab_layout" value="vertical"{}> Sidebar</label>
+</div>
+<div class="section">
+  <h2>Background Tabs</h2>
+  <label>Unload after <input type="number" name="discard_after" value="{}" min="0" style="width:5em"> minutes idle</label>
+  <label><input type="radio" name="discard_low_memory" value="on"{}> Unload when memory is low</label>
+  <label><input type="radio" name="discard_low_memory" value="off"{}> Keep loaded when memory is low</label>
+  <p class="note">0 minutes never unloads idle tabs. Pinned tabs and tabs playing audio are always kept.</p>
+</div>
+<div class="section">
+  <h2>Closing Windows</h2>
+  <label><input type="radio" name="confirm_close" value="on"{}> Ask before closing more than <input type="number" name="confirm_close_tabs" value="{}" min="0" style="width:4em"> tabs or while downloading</label>
+  <label><input type="radio" name="confirm_close" value="off"{}> Close without asking</label>
+  <p class="note">Pages with unsaved changes in forms are always confirmed.</p>
+</div>
 <div class="section">
   <h2>Cache</h2>
   <label><input type="radio" name="cache" value="enabled"{}> Enabled</label>
   <label><input type="radio" name="cache" value="disabled"{}> Disabled</label>
   <p class="note">Takes effect on next launch.</p>
 </div>
+<div class="section">
+  <h2>HTTPS-Only Mode</h2>
+  <label><input type="radio" name="https" value="on"{}> Upgrade to HTTPS</label>
+  <label><input type="radio" name="https" value="off"{}> Allow HTTP</label>
+  <p class="note">Local network addresses are never upgraded.</p>
+</div>
+<div class="section">
+  <h2>Cookie Policy</h2>
+  <label><input type="radio" name="cookies" value="all"{}> Accept all</label>
+  <label><input type="radio" name="cookies" value="no-third-party"{}> Block third-party</label>
+  <label><input type="radio" name="cookies" value="none"{}> Block all</label>
+</div>
+<div class="section">
+  <h2>Tracking Prevention</h2>
+  <label><input type="radio" name="itp" value="on"{}> On</label>
+  <label><input type="radio" name="itp" value="off"{}> Off</label>
+</div>
+<div class="section">
+  <h2>Privacy Signals</h2>
+  <label><input type="radio" name="gpc" value="on"{}> Send Global Privacy Control</label>
+  <label><input type="radio" name="gpc" value="off"{}> Don't send GPC</label>
+  <label><input type="radio" name="dnt" value="on"{}> Send Do Not Track</label>
+  <label><input type="radio" name="dnt" value="off"{}> Don't send DNT</label>
+  <p class="note">Pages read these from navigator; WebKit gives no way to add them to requests.</p>
+</div>
+<div class="section">
+  <h2>Tracking Parameters</h2>
+  <label><input type="radio" name="strip_tracking" value="on"{}> Remove from links</label>
+  <label><input type="radio" name="strip_tracking" value="off"{}> Keep</label>
+  <input type="text" name="tracking_params" value="{}" placeholder="utm_*, fbclid, gclid">
+  <p class="note">Comma separated; a trailing * matches any suffix.</p>
+</div>
+<div class="section">
+  <h2>User Agent</h2>
+  <input type="text" name="user_agent" value="{}" placeholder="Default">
+</div>
+<div class="section">
+  <h2>Proxy</h2>
+  <label><input type="radio" name="proxy_mode" value="system"{}> System settings</label>
+  <label><input type="radio" name="proxy_mode" value="none"{}> No proxy</label>
+  <label><input type="radio" name="proxy_mode" value="manual"{}> Manual</label>
+  <label><input type="radio" name="proxy_mode" value="pac"{}> Automatic (PAC)</label>
+  <div id="proxy-manual" style="display:{}">
+    <input type="text" name="proxy_http" value="{}" placeholder="HTTP proxy, host:port">
+    <input type="text" name="proxy_https" value="{}" placeholder="HTTPS proxy, host:port">
+    <input type="text" name="proxy_socks" value="{}" placeholder="SOCKS proxy, host:port">
+    <input type="text" name="proxy_ignore" value="{}" placeholder="No proxy for: localhost, *.corp.example">
+  </div>
+  <div id="proxy-pac" style="display:{}">
+    <input type="text" name="proxy_pac" value="{}" placeholder="https://example.com/proxy.pac">
+    <p class="note">Takes effect on next launch.</p>
+  </div>
+</div>
 </form>
 <div class="col">
 <div class="section">
   <h2>History</h2>
-  {}<a class="btn" href="rug://settings?clear=1">Clear History</a>
+  {}<a class="btn" href="rug://settings?nonce={nonce}&amp;clear=1">Clear History</a>
 </div>
 <div class="section">
   <h2>Cache Data</h2>
-  {}<a class="btn" href="rug://settings?clear_cache=1">Clear Cache</a>
+  {}<a class="btn" href="rug://settings?nonce={nonce}&amp;clear_cache=1">Clear Cache</a>
 </div>
 <div class="section">
   <h2>Cookies</h2>
-  {}<a class="btn" href="rug://settings?clear_cookies=1">Clear Cookies</a>
+  {}<a class="btn" href="rug://settings?nonce={nonce}&amp;clear_cookies=1">Clear Cookies</a>
+  {}<form method="get" action="rug://settings" class="site-rule">
+    <input type="hidden" name="nonce" value="{nonce}">
+    <input type="text" name="cookie_site" placeholder="example.com">
+    <button name="cookie_rule" value="allow">Allow</button>
+    <button name="cookie_rule" value="block">Block</button>
+  </form>
+  <p class="note">Cookies from blocked sites, and from every unlisted site under Block all with allowed sites, are deleted as soon as they are stored; requests sent before then may still carry them.</p>
+</div>
+{}<div class="section">
+  <h2>Site User Agents</h2>
+  {}<form method="get" action="rug://settings" class="site-rule">
+    <input type="hidden" name="nonce" value="{nonce}">
+    <input type="text" name="ua_site" placeholder="example.com">
+    <select name="ua_preset">
+      <option value="firefox">Firefox</option>
+      <option value="chrome">Chrome</option>
+      <option value="safari-mobile">Mobile Safari</option>
+    </select>
+    <button>Add</button>
+  </form>
+</div>
+<div class="section" id="containers">
+  <h2>Containers</h2>
+  <ul class="hosts">{}</ul>
+  <form method="get" action="rug://settings" class="site-rule">
+    <input type="hidden" name="nonce" value="{nonce}">
+    <input type="text" name="container_name" placeholder="Name">
+    <input type="color" name="container_color" value="#3b82f6">
+    <button>Add</button>
+  </form>
+  <ul class="hosts rules">{}</ul>
+  <form method="get" action="rug://settings" class="site-rule">
+    <input type="hidden" name="nonce" value="{nonce}">
+    <input type="text" name="container_site" placeholder="example.com">
+    <select name="container_assign">{}</select>
+    <button>Assign</button>
+  </form>
+  <p class="note">Assigned sites always open in their container.</p>
+</div>
+<div class="section">
+  <h2>Site Tracking Parameters</h2>
+  {}<form method="get" action="rug://settings" class="site-rule">
+    <input type="hidden" name="nonce" value="{nonce}">
+    <input type="text" name="tracking_site" placeholder="example.com">
+    <input type="text" name="tracking_site_params" placeholder="ref, si">
+    <button>Add</button>
+  </form>
+</div>
+<div class="section">
+  <h2>Site Permissions</h2>
+  <a class="btn" href="rug://permissions">Manage Permissions</a>
+</div>
+<div class="section">
+  <h2>Passwords</h2>
+  <a class="btn" href="rug://passwords">Saved Passwords</a>
+</div>
+<div class="section">
+  <h2>Keyboard</h2>
+  <a class="btn" href="rug://shortcuts">Keyboard Shortcuts</a>
 </div>
 <button type="submit" form="settings-form" id="apply" disabled style="margin-top:auto;margin-bottom:20px">Apply Changes</button>
 </div>
@@ -163,37 +381,369 @@ button.dirty:hover{{background:#2563eb}}
 (function(){{
   var btn = document.getElementById('apply');
   var init = {{}};
-  document.querySelectorAll('input[name=engine],input[name=theme],input[name=cache]').forEach(function(r){{
+  document.querySelectorAll('input[type=radio]').forEach(function(r){{
     if(r.checked) init[r.name] = r.value;
   }});
-  var cu = document.querySelector('input[name=custom_url]');
-  if(cu) init['custom_url'] = cu.value;
+  var texts = document.querySelectorAll('#settings-form input[type=text]');
+  texts.forEach(function(t){{ init[t.name] = t.value; }});
   function check(){{
     var dirty = false;
-    document.querySelectorAll('input[name=engine],input[name=theme],input[name=cache]').forEach(function(r){{
+    document.querySelectorAll('input[type=radio]').forEach(function(r){{
       if(r.checked && init[r.name] !== r.value) dirty = true;
     }});
-    var cu = document.querySelector('input[name=custom_url]');
-    if(cu && cu.value !== init['custom_url']) dirty = true;
+    texts.forEach(function(t){{
+      if(t.value !== init[t.name]) dirty = true;
+    }});
     btn.classList.toggle('dirty', dirty);
     btn.disabled = !dirty;
   }}
-  document.querySelectorAll('input[name=engine],input[name=theme],input[name=cache]').forEach(function(r){{
+  document.querySelectorAll('input[type=radio]').forEach(function(r){{
     r.addEventListener('change', function(){{
       if(r.name === 'engine') document.getElementById('cr').style.display = document.querySelector('input[value=custom]').checked ? 'block' : 'none';
+      if(r.name === 'proxy_mode') {{
+        document.getElementById('proxy-manual').style.display = r.value === 'manual' ? 'block' : 'none';
+        document.getElementById('proxy-pac').style.display = r.value === 'pac' ? 'block' : 'none';
+      }}
       check();
     }});
   }});
-  if(cu) cu.addEventListener('input', check);
+  texts.forEach(function(t){{ t.addEventListener('input', check); }});
 }})();
 </script>
-</body></html>"#,
+</body></html>"##,
         dark_css,
         chk("ddg"), chk("google"), chk("bing"), chk("custom"),
         custom_display, esc(custom_url),
         if dark { "" } else { " checked" }, if dark { " checked" } else { "" },
+        if page.vertical_tabs { "" } else { " checked" }, if page.vertical_tabs { " checked" } else { "" },
+        page.discard_after_minutes,
+        if page.discard_on_low_memory { " checked" } else { "" }, if !page.discard_on_low_memory { " checked" } else { "" },
+        if page.confirm_close { " checked" } else { "" }, page.confirm_close_tabs, if !page.confirm_close { " checked" } else { "" },
         if cache_enabled { " checked" } else { "" }, if !cache_enabled { " checked" } else { "" },
-        msg, cache_msg, cookies_msg)
+        if page.https_only { " checked" } else { "" }, if !page.https_only { " checked" } else { "" },
+        cookie_chk("all"), cookie_chk("no-third-party"), cookie_chk("none"),
+        if page.itp { " checked" } else { "" }, if !page.itp { " checked" } else { "" },
+        if page.gpc { " checked" } else { "" }, if !page.gpc { " checked" } else { "" },
+        if page.dnt { " checked" } else { "" }, if !page.dnt { " checked" } else { "" },
+        if page.strip_tracking { " checked" } else { "" }, if !page.strip_tracking { " checked" } else { "" },
+        esc(page.tracking_params),
+        esc(page.user_agent),
+        proxy_chk("system"), proxy_chk("none"), proxy_chk("manual"), proxy_chk("pac"),
+        if page.proxy_mode == "manual" { "block" } else { "none" },
+        esc(page.proxy_http), esc(page.proxy_https), esc(page.proxy_socks), esc(page.proxy_ignore),
+        if page.proxy_mode == "pac" { "block" } else { "none" }, esc(page.proxy_pac),
+        msg, cache_msg, cookies_msg, cookie_rules, site_user_agents,
+        containers, container_sites, container_options, site_tracking_params, exceptions,
+        nonce = esc(nonce))
+}
+
+pub fn https_error(url: &str, host: &str, proceed_href: &str, dark: bool) -> String {
+    let dark_css = if dark { r"
+body{background:#18181b;color:#e4e4e7}
+p{color:#a1a1aa}
+code{background:#27272a}
+.btn{background:#27272a;color:#e4e4e7}
+.btn:hover{background:#3f3f46}
+.secondary{color:#71717a}
+" } else { "" };
+    format!(r#"<!DOCTYPE html>
+<html lang="en">
+<head><meta charset="UTF-8"><title>Secure connection unavailable</title><style>
+body{{margin:0;font-family:Arial,sans-serif;display:flex;flex-direction:column;align-items:center;padding-top:15vh;background:#fff;color:#333}}
+.box{{max-width:520px;padding:0 24px}}
+h1{{font-size:1.6em;margin:0 0 .75em}}
+p{{line-height:1.5;color:#555}}
+code{{background:#f0f0f0;padding:1px 5px;border-radius:4px;word-break:break-all}}
+.actions{{display:flex;gap:12px;align-items:center;margin-top:24px}}
+.btn{{display:inline-block;padding:7px 18px;background:#e0e0e0;border-radius:6px;color:#333;text-decoration:none;font-size:.9em}}
+.btn:hover{{background:#d0d0d0}}
+.secondary{{color:#999;font-size:.875em}}
+{}</style></head>
+<body><div class="box">
+<h1>Secure connection unavailable</h1>
+<p>HTTPS-Only Mode is on, but <code>{}</code> could not be reached over HTTPS.</p>
+<p>If you continue, the page will load over an unencrypted connection and anyone on the network can read or modify it. Rug will remember this choice for <code>{}</code>.</p>
+<p><code>{}</code></p>
+<div class="actions">
+<a class="btn" href="rug://home" onclick="if(history.length>1){{history.back();return false}}">Go Back</a>
+<a class="secondary" href="{}">Continue over HTTP</a>
+</div>
+</div></body></html>"#, dark_css, esc(host), esc(host), esc(url), esc(proceed_href))
+}
+
+pub fn permissions(entries: &[(String, String, bool)], nonce: &str, dark: bool) -> String {
+    let dark_css = if dark { r"
+body{background:#18181b;color:#e4e4e7}
+th{color:#52525b}
+td{border-color:#3f3f46}
+a{color:#a1a1aa}
+.allowed{color:#4ade80}
+.denied{color:#f87171}
+.empty{color:#52525b}
+.home{color:#52525b}
+.home:hover{color:#e4e4e7}
+" } else { "" };
+    let mut rows = String::new();
+    for (origin, kind, allowed) in entries {
+        let q = format!("nonce={}&amp;origin={}&amp;kind={}", esc(nonce), esc(&query_escape(origin)), esc(kind));
+        let (state, class, flip, flip_label) = if *allowed {
+            ("Allowed", "allowed", "deny", "Block")
+        } else {
+            ("Blocked", "denied", "allow", "Allow")
+        };
+        rows.push_str(&format!(
+            "<tr><td>{}</td><td>{}</td><td class=\"{}\">{}</td><td><a href=\"rug://permissions?{}&amp;set={}\">{}</a> <a href=\"rug://permissions?{}&amp;set=forget\">Forget</a></td></tr>",
+            esc(origin), permission_name(kind), class, state, q, flip, flip_label, q
+        ));
+    }
+    let body = if rows.is_empty() {
+        String::from("<p class=\"empty\">No sites have saved permissions.</p>")
+    } else {
+        format!("<table><tr><th>Site</th><th>Permission</th><th>Decision</th><th></th></tr>{}</table>", rows)
+    };
+    format!(r#"<!DOCTYPE html>
+<html lang="en">
+<head><meta charset="UTF-8"><title>permissions</title><style>
+body{{margin:0;font-family:Arial,sans-serif;display:flex;flex-direction:column;align-items:center;padding-top:10vh;background:#fff}}
+h1{{font-size:3em;margin:0 0 .75em}}
+table{{border-collapse:collapse;font-size:.9em}}
+th{{text-align:left;font-size:.8em;color:#999;text-transform:uppercase;letter-spacing:.08em;font-weight:600;padding:0 16px 8px 0}}
+td{{padding:6px 16px 6px 0;border-top:1px solid #e0e0e0}}
+a{{color:#555;margin-right:8px}}
+.allowed{{color:#16a34a}}
+.denied{{color:#dc2626}}
+.empty{{color:#999}}
+.home{{position:fixed;top:16px;right:24px;color:#aaa;font-size:.875em;text-decoration:none}}
+.home:hover{{color:#333}}
+{}</style></head>
+<body><a class="home" href="rug://settings">Settings</a><h1>permissions</h1>
+{}
+</body></html>"#, dark_css, body)
+}
+
+pub fn shortcuts(entries: &[(String, String)], invalid: &[(String, String)], config_path: &str, dark: bool) -> String {
+    let dark_css = if dark { r"
+body{background:#18181b;color:#e4e4e7}
+td{border-color:#3f3f46}
+kbd{background:#27272a;border-color:#3f3f46}
+.note{color:#71717a}
+.warning{color:#f87171}
+.home{color:#52525b}
+.home:hover{color:#e4e4e7}
+" } else { "" };
+    let rows: String = entries.iter().map(|(action, keys)| {
+        let keys = if keys.is_empty() { String::from("<span class=\"note\">Unbound</span>") } else {
+            keys.split(", ").map(|k| format!("<kbd>{}</kbd>", esc(k))).collect::<Vec<_>>().join(" ")
+        };
+        format!("<tr><td>{}</td><td>{}</td></tr>", esc(action), keys)
+    }).collect();
+    let warning = if invalid.is_empty() { String::new() } else {
+        let items: String = invalid.iter().map(|(action, accel)| format!("<li><kbd>{}</kbd> for {}</li>", esc(accel), esc(action))).collect();
+        format!("<div class=\"warning\">Ignored unknown keys in {}:<ul>{}</ul></div>", esc(config_path), items)
+    };
+    format!(r#"<!DOCTYPE html>
+<html lang="en">
+<head><meta charset="UTF-8"><title>shortcuts</title><style>
+body{{margin:0;font-family:Arial,sans-serif;display:flex;flex-direction:column;align-items:center;padding-top:10vh;background:#fff}}
+h1{{font-size:3em;margin:0 0 .75em}}
+table{{border-collapse:collapse;font-size:.9em}}
+td{{padding:6px 16px 6px 0;border-top:1px solid #e0e0e0}}
+kbd{{font-family:inherit;font-size:.85em;background:#f4f4f5;border:1px solid #d4d4d8;border-radius:4px;padding:1px 6px}}
+.note{{color:#999;font-size:.85em;max-width:32em}}
+.warning{{color:#dc2626;font-size:.85em;max-width:32em;margin-bottom:1em}}
+.home{{position:fixed;top:16px;right:24px;color:#aaa;font-size:.875em;text-decoration:none}}
+.home:hover{{color:#333}}
+{}</style></head>
+<body><a class="home" href="rug://settings">Settings</a><h1>shortcuts</h1>
+{}<table>{}</table>
+<p class="note">To change a shortcut, map its action to one or more keys in {}, for example {{"reload": ["F5", "&lt;Control&gt;r"]}}, then restart rug.</p>
+</body></html>"#, dark_css, warning, rows, esc(config_path))
+}
+
+pub fn passwords(unlocked: bool, logins: &[(String, String)], revealed: Option<&(String, String, String)>, never: &[String], notice: Option<&str>, nonce: &str, dark: bool) -> String {
+    let dark_css = if dark { r"
+body{background:#18181b;color:#e4e4e7}
+th{color:#52525b}
+td{border-color:#3f3f46}
+a{color:#a1a1aa}
+input{background:#27272a;color:#e4e4e7;border-color:#3f3f46}
+button{background:#27272a;color:#e4e4e7;border-color:#3f3f46}
+.notice{color:#a1a1aa}
+.empty{color:#52525b}
+.home{color:#52525b}
+.home:hover{color:#e4e4e7}
+" } else { "" };
+    let mut rows = String::new();
+    for (i, (origin, username)) in logins.iter().enumerate() {
+        let nonce = esc(nonce);
+        let q = format!("nonce={}&amp;origin={}&amp;username={}", nonce, esc(&query_escape(origin)), esc(&query_escape(username)));
+        let key = format!("<input type=\"hidden\" name=\"nonce\" value=\"{}\"><input type=\"hidden\" name=\"origin\" value=\"{}\">\
+<input type=\"hidden\" name=\"username\" value=\"{}\">", nonce, esc(origin), esc(username));
+        // Passwords stay on the Rust side until Show is posted for their row
+        let password = match revealed.filter(|(o, u, _)| o == origin && u == username) {
+            Some((_, _, password)) => format!(
+                "<input form=\"p{i}\" type=\"text\" name=\"password\" value=\"{}\"> <a href=\"rug://passwords\">Hide</a>", esc(password)
+            ),
+            None => format!(
+                "<input form=\"p{i}\" type=\"password\" name=\"password\" placeholder=\"Unchanged\"> \
+<form class=\"inline\" method=\"post\" action=\"rug://passwords\">{key}<button name=\"action\" value=\"reveal\">Show</button></form>"
+            ),
+        };
+        rows.push_str(&format!(
+            "<tr><td>{origin}</td><td><input form=\"p{i}\" name=\"new_username\" value=\"{username}\"></td><td>{password}</td>\
+<td><form id=\"p{i}\" method=\"post\" action=\"rug://passwords\">{key}<input type=\"hidden\" name=\"action\" value=\"save\">\
+<button type=\"submit\">Save</button> <a href=\"rug://passwords?{q}&amp;action=delete\">Delete</a></form></td></tr>",
+            origin = esc(origin), username = esc(username), password = password, i = i, q = q, key = key
+        ));
+    }
+    let body = if !unlocked {
+        format!("<p class=\"empty\">Saved passwords are locked.</p><a href=\"rug://passwords?nonce={}&amp;unlock=1\">Unlock</a>", esc(nonce))
+    } else if rows.is_empty() {
+        String::from("<p class=\"empty\">No saved passwords.</p>")
+    } else {
+        format!("<table><tr><th>Site</th><th>Username</th><th>Password</th><th></th></tr>{}</table>\
+<form method=\"post\" action=\"rug://passwords\" onsubmit=\"return confirm('Export all passwords to an unencrypted file?')\">\
+<input type=\"hidden\" name=\"nonce\" value=\"{}\"><p><button name=\"export\" value=\"1\">Export to CSV</button></p></form>", rows, esc(nonce))
+    };
+    let notice = notice.map(|n| format!("<p class=\"notice\">{}</p>", esc(n))).unwrap_or_default();
+    let never = if never.is_empty() { String::new() } else {
+        let items: String = never.iter().map(|o| format!(
+            "<tr><td>{}</td><td><a href=\"rug://passwords?nonce={}&amp;remove_never={}\">Remove</a></td></tr>",
+            esc(o), esc(nonce), esc(&query_escape(o))
+        )).collect();
+        format!("<h2>Never saved</h2><table>{}</table>", items)
+    };
+    format!(r#"<!DOCTYPE html>
+<html lang="en">
+<head><meta charset="UTF-8"><title>passwords</title><style>
+body{{margin:0;font-family:Arial,sans-serif;display:flex;flex-direction:column;align-items:center;padding-top:10vh;background:#fff}}
+h1{{font-size:3em;margin:0 0 .75em}}
+h2{{font-size:.8em;color:#999;text-transform:uppercase;letter-spacing:.08em;margin:2em 0 .5em}}
+table{{border-collapse:collapse;font-size:.9em}}
+th{{text-align:left;font-size:.8em;color:#999;text-transform:uppercase;letter-spacing:.08em;font-weight:600;padding:0 16px 8px 0}}
+td{{padding:6px 16px 6px 0;border-top:1px solid #e0e0e0}}
+input{{font-size:.9em;padding:3px 6px;border:1px solid #ccc;border-radius:4px}}
+button{{font-size:.85em;padding:3px 8px;border:1px solid #ccc;border-radius:4px;background:#f5f5f5;cursor:pointer}}
+a{{color:#555;margin-right:8px}}
+.notice{{color:#555;font-size:.9em}}
+.empty{{color:#999}}
+form.inline{{display:inline}}
+.home{{position:fixed;top:16px;right:24px;color:#aaa;font-size:.875em;text-decoration:none}}
+.home:hover{{color:#333}}
+{}</style></head>
+<body><a class="home" href="rug://settings">Settings</a><h1>passwords</h1>
+{}{}{}
+</body></html>"#, dark_css, notice, body, never)
+}
+
+fn permission_name(kind: &str) -> &'static str {
+    match kind {
+        "geolocation" => "Location",
+        "notifications" => "Notifications",
+        "camera" => "Camera",
+        "microphone" => "Microphone",
+        "camera-microphone" => "Camera and microphone",
+        "screen" => "Screen sharing",
+        "clipboard" => "Clipboard",
+        "device-info" => "Media devices",
+        "pointer-lock" => "Pointer lock",
+        "media-keys" => "Protected content",
+        "storage-access" => "Cross-site cookies",
+        _ => "Other",
+    }
+}
+
+fn user_agent_name(preset: &str) -> &'static str {
+    match preset {
+        "firefox" => "Firefox",
+        "chrome" => "Chrome",
+        "safari-mobile" => "Mobile Safari",
+        _ => "Default",
+    }
+}
+
+fn query_escape(s: &str) -> String {
+    s.replace('%', "%25").replace('&', "%26").replace('+', "%2B").replace('#', "%23").replace(' ', "%20")
+}
+
+fn host_section(title: &str, hosts: &[String], remove_param: &str, note: &str, nonce: &str) -> String {
+    if hosts.is_empty() { return String::new(); }
+    let rows: String = hosts.iter().map(|h| format!(
+        "<li><span>{}</span><a href=\"rug://settings?nonce={}&amp;{}={}\">Remove</a></li>",
+        esc(h), esc(nonce), remove_param, esc(h)
+    )).collect();
+    let note = if note.is_empty() { String::new() } else { format!("<p class=\"note\">{}</p>", note) };
+    format!("<div class=\"section\">\n  <h2>{}</h2>\n  <ul class=\"hosts\">{}</ul>{}\n</div>\n", title, rows, note)
+}
+
+pub fn redirect(url: &str) -> String {
+    format!("<!DOCTYPE html><html><head><meta http-equiv=\"refresh\" content=\"0;url={}\"></head><body></body></html>", esc(url))
+}
+
+pub struct CertificateDetails {
+    pub subject: String,
+    pub issuer: String,
+    pub not_before: String,
+    pub not_after: String,
+    pub pem: String,
+}
+
+pub fn tls_error(url: &str, host: &str, errors: &[&str], chain: &[CertificateDetails], session_href: &str, always_href: &str, dark: bool) -> String {
+    let dark_css = if dark { r"
+body{background:#18181b;color:#e4e4e7}
+p,li{color:#a1a1aa}
+code,pre{background:#27272a}
+details{border-color:#3f3f46}
+th{color:#71717a}
+.btn{background:#27272a;color:#e4e4e7}
+.btn:hover{background:#3f3f46}
+.secondary{color:#71717a}
+" } else { "" };
+    let errors: String = errors.iter().map(|e| format!("<li>{}</li>", esc(e))).collect();
+    let mut certs = String::new();
+    for (i, cert) in chain.iter().enumerate() {
+        certs.push_str(&format!(
+            "<details{}><summary>{}</summary><table>\
+<tr><th>Subject</th><td>{}</td></tr><tr><th>Issuer</th><td>{}</td></tr>\
+<tr><th>Valid from</th><td>{}</td></tr><tr><th>Valid until</th><td>{}</td></tr></table>\
+<pre>{}</pre></details>",
+            if i == 0 { " open" } else { "" }, esc(&cert.subject), esc(&cert.subject), esc(&cert.issuer),
+            esc(&cert.not_before), esc(&cert.not_after), esc(&cert.pem)
+        ));
+    }
+    format!(r#"<!DOCTYPE html>
+<html lang="en">
+<head><meta charset="UTF-8"><title>Certificate error</title><style>
+body{{margin:0;font-family:Arial,sans-serif;display:flex;flex-direction:column;align-items:center;padding:10vh 0;background:#fff;color:#333}}
+.box{{max-width:640px;padding:0 24px}}
+h1{{font-size:1.6em;margin:0 0 .75em}}
+h2{{font-size:.85em;color:#999;text-transform:uppercase;letter-spacing:.08em;margin:24px 0 10px;font-weight:600}}
+p,li{{line-height:1.5;color:#555}}
+code{{background:#f0f0f0;padding:1px 5px;border-radius:4px;word-break:break-all}}
+details{{border:1px solid #e0e0e0;border-radius:6px;padding:8px 12px;margin-bottom:8px}}
+summary{{cursor:pointer;font-size:.9em;word-break:break-all}}
+table{{font-size:.85em;margin-top:8px;border-spacing:0 4px}}
+th{{text-align:left;color:#999;font-weight:normal;padding-right:16px;white-space:nowrap;vertical-align:top}}
+td{{word-break:break-all}}
+pre{{font-size:.7em;background:#f0f0f0;padding:8px;border-radius:4px;overflow-x:auto}}
+.actions{{display:flex;gap:16px;align-items:center;margin-top:24px}}
+.btn{{display:inline-block;padding:7px 18px;background:#e0e0e0;border-radius:6px;color:#333;text-decoration:none;font-size:.9em}}
+.btn:hover{{background:#d0d0d0}}
+.secondary{{color:#999;font-size:.875em}}
+{}</style></head>
+<body><div class="box">
+<h1>This connection is not trusted</h1>
+<p>The certificate presented by <code>{}</code> failed verification:</p>
+<ul>{}</ul>
+<p><code>{}</code></p>
+<h2>Certificate chain</h2>
+{}
+<div class="actions">
+<a class="btn" href="rug://home" onclick="if(history.length>1){{history.back();return false}}">Go Back</a>
+<a class="secondary" href="{}">Proceed anyway</a>
+<a class="secondary" href="{}">Always trust this certificate for {}</a>
+</div>
+</div></body></html>"#, dark_css, esc(host), errors, esc(url), certs, esc(session_href), esc(always_href), esc(host))
 }
 
 fn favicon_url(url: &str) -> Option<String> {
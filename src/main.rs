@@ -7,21 +7,81 @@ use glib::clone;
 use std::cell::RefCell;
 
 mod html;
+mod passwords;
 
 thread_local! {
-    static RECENT_PAGES: RefCell<Vec<(String, String, Option<String>)>> = RefCell::new(Vec::new());
+    static RECENT_PAGES: RefCell<Vec<(String, String, Option<String>)>> = const { RefCell::new(Vec::new()) };
     static FAVICON_CACHE: RefCell<std::collections::HashMap<String, gtk4::gdk::Texture>> =
         RefCell::new(std::collections::HashMap::new());
     static SEARCH_ENGINE: RefCell<String> = RefCell::new("ddg".to_string());
-    static CUSTOM_SEARCH_URL: RefCell<String> = RefCell::new(String::new());
-    static DARK_MODE: RefCell<bool> = RefCell::new(false);
-    static CACHE_ENABLED: RefCell<bool> = RefCell::new(true);
-    static NETWORK_SESSION: RefCell<Option<webkit6::NetworkSession>> = RefCell::new(None);
-    static PRIVATE: RefCell<bool> = RefCell::new(false);
+    static CUSTOM_SEARCH_URL: RefCell<String> = const { RefCell::new(String::new()) };
+    static DARK_MODE: RefCell<bool> = const { RefCell::new(false) };
+    static CACHE_ENABLED: RefCell<bool> = const { RefCell::new(true) };
+    static NETWORK_SESSION: RefCell<Option<webkit6::NetworkSession>> = const { RefCell::new(None) };
+    static PRIVATE_SESSION: RefCell<Option<webkit6::NetworkSession>> = const { RefCell::new(None) };
+    static HTTPS_ONLY: RefCell<bool> = const { RefCell::new(false) };
+    static HTTPS_EXCEPTIONS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    static TLS_ERRORS: RefCell<std::collections::HashMap<String, (gtk4::gio::TlsCertificate, gtk4::gio::TlsCertificateFlags)>> =
+        RefCell::new(std::collections::HashMap::new());
+    static TLS_EXCEPTIONS: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
+    static PERMISSIONS: RefCell<Vec<(String, String, bool)>> = const { RefCell::new(Vec::new()) };
+    static PAGE_NONCE: String = glib::uuid_string_random().to_string();
+    static DEFAULT_USER_AGENT: String = webkit6::Settings::new().user_agent().map(|u| u.to_string()).unwrap_or_default();
+    static COOKIE_POLICY: RefCell<String> = RefCell::new("no-third-party".to_string());
+    static ITP_ENABLED: RefCell<bool> = const { RefCell::new(false) };
+    static COOKIE_EXCEPTIONS: RefCell<Vec<(String, bool)>> = const { RefCell::new(Vec::new()) };
+    static PROXY_MODE: RefCell<String> = RefCell::new("system".to_string());
+    static PROXY_HTTP: RefCell<String> = const { RefCell::new(String::new()) };
+    static PROXY_HTTPS: RefCell<String> = const { RefCell::new(String::new()) };
+    static PROXY_SOCKS: RefCell<String> = const { RefCell::new(String::new()) };
+    static PROXY_IGNORE: RefCell<String> = RefCell::new("localhost, 127.0.0.1, ::1".to_string());
+    static PROXY_PAC: RefCell<String> = const { RefCell::new(String::new()) };
+    static SEND_GPC: RefCell<bool> = const { RefCell::new(false) };
+    static SEND_DNT: RefCell<bool> = const { RefCell::new(false) };
+    static CUSTOM_USER_AGENT: RefCell<String> = const { RefCell::new(String::new()) };
+    static SITE_USER_AGENTS: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
+    static PASSWORD_NEVER_SAVE: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    // Decisions made in private windows are kept until quit and never saved
+    static PRIVATE_PERMISSIONS: RefCell<Vec<(String, String, bool)>> = const { RefCell::new(Vec::new()) };
+    static PRIVATE_HTTPS_EXCEPTIONS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    static PRIVATE_NEVER_SAVE: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    static PRIVATE_MUTED_SITES: RefCell<Vec<(String, bool)>> = const { RefCell::new(Vec::new()) };
+    static STRIP_TRACKING: RefCell<bool> = const { RefCell::new(true) };
+    static TRACKING_PARAMS: RefCell<String> = RefCell::new(DEFAULT_TRACKING_PARAMS.to_string());
+    static SITE_TRACKING_PARAMS: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
+    // Tracked and clean URI of the navigation whose policy is being decided, see add_tab
+    static CLEAN_NAVIGATION: RefCell<Option<(String, String)>> = const { RefCell::new(None) };
+    static ALLOWED_SCHEMES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    static CONTAINERS: RefCell<Vec<(String, String)>> = RefCell::new(
+        DEFAULT_CONTAINERS.iter().map(|(n, c)| (n.to_string(), c.to_string())).collect()
+    );
+    static CONTAINER_SITES: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
+    static CONTAINER_IDS: RefCell<std::collections::HashMap<String, String>> = RefCell::new(std::collections::HashMap::new());
+    static VERTICAL_TABS: RefCell<bool> = const { RefCell::new(false) };
+    static SIDEBAR_COLLAPSED: RefCell<bool> = const { RefCell::new(false) };
+    static PINNED_TABS: RefCell<Vec<SavedTab>> = const { RefCell::new(Vec::new()) };
+    static TAB_MRU: RefCell<Vec<(glib::WeakRef<WebView>, std::time::Instant)>> = const { RefCell::new(Vec::new()) };
+    static CONFIRM_CLOSE: RefCell<bool> = const { RefCell::new(true) };
+    static CONFIRM_CLOSE_TABS: RefCell<u32> = const { RefCell::new(1) };
+    static ACTIVE_DOWNLOADS: RefCell<u32> = const { RefCell::new(0) };
+    static MUTED_SITES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    static DISCARDED_TABS: RefCell<Vec<glib::WeakRef<WebView>>> = const { RefCell::new(Vec::new()) };
+    // Views built with `related_view` share their opener's web process
+    static SHARED_PROCESSES: RefCell<Vec<Vec<glib::WeakRef<WebView>>>> = const { RefCell::new(Vec::new()) };
+    static DISCARD_AFTER_MINUTES: RefCell<u32> = const { RefCell::new(30) };
+    static DISCARD_ON_LOW_MEMORY: RefCell<bool> = const { RefCell::new(true) };
+    static TAB_GROUPS: RefCell<Vec<TabGroup>> = const { RefCell::new(Vec::new()) };
+    static SAVED_GROUPS: RefCell<Vec<TabGroup>> = const { RefCell::new(Vec::new()) };
+    static NEXT_GROUP_ID: RefCell<u32> = const { RefCell::new(1) };
+    static CONTAINER_SESSIONS: RefCell<std::collections::HashMap<String, webkit6::NetworkSession>> =
+        RefCell::new(std::collections::HashMap::new());
+    static WINDOWS: RefCell<Vec<BrowserWindow>> = const { RefCell::new(Vec::new()) };
+    static KIOSK: RefCell<bool> = const { RefCell::new(false) };
+    static KEYBINDINGS: RefCell<Vec<(String, Vec<String>)>> = const { RefCell::new(Vec::new()) };
+    static INVALID_KEYBINDINGS: RefCell<Vec<(String, String)>> = const { RefCell::new(Vec::new()) };
 }
 
 fn update_recent(url: &str, title: &str) {
-    if PRIVATE.with(|i| *i.borrow()) { return; }
     if url.is_empty() || url.starts_with("about:") || url.starts_with("rug:") { return; }
     RECENT_PAGES.with(|rp| {
         let mut pages = rp.borrow_mut();
@@ -39,7 +99,6 @@ fn update_recent(url: &str, title: &str) {
 }
 
 fn update_recent_favicon(url: &str, texture: &gtk4::gdk::Texture) {
-    if PRIVATE.with(|i| *i.borrow()) { return; }
     if url.is_empty() || url.starts_with("about:") || url.starts_with("rug:") { return; }
     let data_uri = texture_to_data_uri(texture);
     RECENT_PAGES.with(|rp| {
@@ -81,7 +140,7 @@ fn url_decode(s: &str) -> String {
 
 fn parse_query_params(uri: &str) -> std::collections::HashMap<String, String> {
     let mut map = std::collections::HashMap::new();
-    if let Some(q) = uri.splitn(2, '?').nth(1) {
+    if let Some((_, q)) = uri.split_once('?') {
         for pair in q.split('&') {
             if let Some((k, v)) = pair.split_once('=') {
                 map.insert(url_decode(k), url_decode(v));
@@ -91,6 +150,15 @@ fn parse_query_params(uri: &str) -> std::collections::HashMap<String, String> {
     map
 }
 
+// Links and forms on rug pages carry this, so other pages can't change settings by linking there
+fn page_nonce() -> String {
+    PAGE_NONCE.with(|n| n.clone())
+}
+
+fn nonce_ok(params: &std::collections::HashMap<String, String>) -> bool {
+    params.get("nonce").is_some_and(|n| *n == page_nonce())
+}
+
 fn data_path() -> std::path::PathBuf {
     #[cfg(debug_assertions)]
     { std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target").join("tmp").join("data.json") }
@@ -105,6 +173,13 @@ fn settings_path() -> std::path::PathBuf {
     { glib::home_dir().join(".config").join("rug").join("settings.json") }
 }
 
+fn keybindings_path() -> std::path::PathBuf {
+    #[cfg(debug_assertions)]
+    { std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target").join("tmp").join("keybindings.json") }
+    #[cfg(not(debug_assertions))]
+    { glib::home_dir().join(".config").join("rug").join("keybindings.json") }
+}
+
 fn wk_cache_path() -> std::path::PathBuf {
     #[cfg(debug_assertions)]
     { std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/tmp/wk_cache") }
@@ -119,6 +194,31 @@ fn cookies_path() -> std::path::PathBuf {
     { glib::home_dir().join(".local/share/rug/cookies.sqlite") }
 }
 
+fn legacy_container_slug(name: &str) -> String {
+    name.chars().map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '-' }).collect()
+}
+
+// Directories are keyed by a generated id, since distinct names can slug alike
+fn container_id(name: &str) -> String {
+    if let Some(id) = CONTAINER_IDS.with(|c| c.borrow().get(name).cloned()) { return id; }
+    let id = glib::uuid_string_random().to_string();
+    CONTAINER_IDS.with(|c| c.borrow_mut().insert(name.to_string(), id.clone()));
+    save_settings();
+    id
+}
+
+fn container_dirs(name: &str) -> (std::path::PathBuf, std::path::PathBuf) {
+    let id = container_id(name);
+    #[cfg(debug_assertions)]
+    let (data, cache) = (
+        std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/tmp/wk_data"),
+        std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/tmp/wk_cache"),
+    );
+    #[cfg(not(debug_assertions))]
+    let (data, cache) = (glib::home_dir().join(".local/share/rug"), glib::home_dir().join(".cache/rug"));
+    (data.join("containers").join(&id), cache.join("containers").join(&id))
+}
+
 fn clear_cache() {
     let path = wk_cache_path();
     let _ = std::fs::remove_dir_all(&path);
@@ -135,19 +235,145 @@ fn apply_dark_mode(dark: bool) {
     }
 }
 
+// Window actions with their default accelerators; keybindings.json overrides them by action name
+// An action and the accelerators bound to it
+type Keybinding = (String, Vec<String>);
+
+const SHORTCUTS: &[(&str, &str, &[&str])] = &[
+    ("win.new-tab", "New tab", &["<Control>t"]),
+    ("win.close-tab", "Close tab", &["<Control>w"]),
+    ("win.new-private-window", "New private window", &["<Control><Shift>n"]),
+    ("win.duplicate-tab", "Duplicate tab", &["<Control><Shift>k"]),
+    ("win.focus-url", "Focus address bar", &["<Control>l", "<Alt>d", "F6"]),
+    ("win.back", "Back", &["<Alt>Left"]),
+    ("win.forward", "Forward", &["<Alt>Right"]),
+    ("win.reload", "Reload", &["F5", "<Control>r"]),
+    ("win.hard-reload", "Reload, bypassing the cache", &["<Shift>F5", "<Control><Shift>r"]),
+    ("win.stop", "Stop loading, or leave the address bar", &["Escape"]),
+    ("win.find", "Find in page", &["<Control>f"]),
+    ("win.zoom-in", "Zoom in", &["<Control>plus", "<Control>equal", "<Control>KP_Add"]),
+    ("win.zoom-out", "Zoom out", &["<Control>minus", "<Control>KP_Subtract"]),
+    ("win.zoom-reset", "Reset zoom", &["<Control>0", "<Control>KP_0"]),
+    ("win.next-tab", "Next tab", &["<Control>Page_Down"]),
+    ("win.previous-tab", "Previous tab", &["<Control>Page_Up"]),
+    ("win.switch-recent-tab", "Switch to recently used tab", &["<Control>Tab"]),
+    ("win.switch-recent-tab-backwards", "Switch to recently used tab, backwards", &["<Control><Shift>Tab", "<Control><Shift>ISO_Left_Tab"]),
+    ("win.go-to-tab(1)", "Go to tab 1", &["<Control>1"]),
+    ("win.go-to-tab(2)", "Go to tab 2", &["<Control>2"]),
+    ("win.go-to-tab(3)", "Go to tab 3", &["<Control>3"]),
+    ("win.go-to-tab(4)", "Go to tab 4", &["<Control>4"]),
+    ("win.go-to-tab(5)", "Go to tab 5", &["<Control>5"]),
+    ("win.go-to-tab(6)", "Go to tab 6", &["<Control>6"]),
+    ("win.go-to-tab(7)", "Go to tab 7", &["<Control>7"]),
+    ("win.go-to-tab(8)", "Go to tab 8", &["<Control>8"]),
+    ("win.go-to-tab(9)", "Go to the last tab", &["<Control>9"]),
+    ("win.search-tabs", "Search tabs", &["<Control><Shift>a"]),
+    ("win.tab-overview", "Tab overview", &["<Control><Shift>o"]),
+    ("win.fullscreen", "Toggle fullscreen", &["F11"]),
+];
+
+// Accelerators fire before the page sees a key, so these are matched after it has had its chance
+const PAGE_FIRST: &[&str] = &["win.stop"];
+
+// A file such as {"win.reload": ["F5"], "find": "<Control>slash"}; an empty list unbinds an action
+// Bindings per action from the config file's content, falling back to the defaults, plus the
+// (description, accelerator) pairs that `valid` rejected
+fn parse_keybindings(content: Option<&str>, valid: impl Fn(&str) -> bool) -> (Vec<Keybinding>, Vec<(String, String)>) {
+    let mut invalid = Vec::new();
+    let overrides = content.and_then(|content| serde_json::from_str::<serde_json::Value>(content).ok());
+    let bindings = SHORTCUTS.iter().map(|(action, description, defaults)| {
+        let configured = overrides.as_ref().and_then(|o| o.get(*action).or_else(|| o.get(action.trim_start_matches("win."))));
+        let accels = match configured {
+            Some(serde_json::Value::String(accel)) => vec![accel.clone()],
+            Some(serde_json::Value::Array(list)) => list.iter().filter_map(|a| a.as_str().map(str::to_string)).collect(),
+            _ => defaults.iter().map(|a| a.to_string()).collect(),
+        };
+        let (accels, unknown): (Vec<String>, Vec<String>) = accels.into_iter().partition(|a| valid(a));
+        invalid.extend(unknown.into_iter().map(|a| (description.to_string(), a)));
+        (action.to_string(), accels)
+    }).collect();
+    (bindings, invalid)
+}
+
+fn load_keybindings(app: &Application) {
+    let content = std::fs::read_to_string(keybindings_path()).ok();
+    let (bindings, invalid) = parse_keybindings(content.as_deref(), |a| gtk4::accelerator_parse(a).is_some());
+    for (action, accels) in &bindings {
+        let accels: Vec<&str> = if PAGE_FIRST.contains(&action.as_str()) { Vec::new() } else { accels.iter().map(String::as_str).collect() };
+        app.set_accels_for_action(action, &accels);
+    }
+    KEYBINDINGS.with(|k| *k.borrow_mut() = bindings);
+    INVALID_KEYBINDINGS.with(|k| *k.borrow_mut() = invalid);
+}
+
+fn page_first_action(key: Key, state: gtk4::gdk::ModifierType) -> Option<String> {
+    let mods = state & gtk4::accelerator_get_default_mod_mask();
+    KEYBINDINGS.with(|k| k.borrow().iter()
+        .filter(|(action, _)| PAGE_FIRST.contains(&action.as_str()))
+        .find(|(_, accels)| accels.iter().filter_map(gtk4::accelerator_parse).any(|(k, m)| k.to_lower() == key.to_lower() && m == mods))
+        .map(|(action, _)| action.clone()))
+}
+
+fn shortcuts_page() -> String {
+    let entries: Vec<(String, String)> = KEYBINDINGS.with(|k| k.borrow().iter().map(|(action, accels)| {
+        let description = SHORTCUTS.iter().find(|(a, _, _)| a == action).map(|(_, d, _)| *d).unwrap_or_default();
+        let keys = accels.iter()
+            .filter_map(gtk4::accelerator_parse)
+            .map(|(key, mods)| gtk4::accelerator_get_label(key, mods).to_string())
+            .collect::<Vec<_>>();
+        (description.to_string(), keys.join(", "))
+    }).collect());
+    let invalid = INVALID_KEYBINDINGS.with(|k| k.borrow().clone());
+    html::shortcuts(&entries, &invalid, &keybindings_path().display().to_string(), DARK_MODE.with(|d| *d.borrow()))
+}
+
 fn save_settings() {
-    let engine = SEARCH_ENGINE.with(|e| e.borrow().clone());
-    let custom_url = CUSTOM_SEARCH_URL.with(|u| u.borrow().clone());
-    let dark = DARK_MODE.with(|d| *d.borrow());
-    let cache = CACHE_ENABLED.with(|c| *c.borrow());
+    let settings = serde_json::json!({
+        "engine": SEARCH_ENGINE.with(|e| e.borrow().clone()),
+        "custom_url": CUSTOM_SEARCH_URL.with(|u| u.borrow().clone()),
+        "dark": DARK_MODE.with(|d| *d.borrow()),
+        "cache": CACHE_ENABLED.with(|c| *c.borrow()),
+        "https_only": HTTPS_ONLY.with(|h| *h.borrow()),
+        "https_exceptions": HTTPS_EXCEPTIONS.with(|h| h.borrow().clone()),
+        "tls_exceptions": TLS_EXCEPTIONS.with(|t| t.borrow().clone()),
+        "permissions": PERMISSIONS.with(|p| p.borrow().clone()),
+        "cookie_policy": COOKIE_POLICY.with(|c| c.borrow().clone()),
+        "itp": ITP_ENABLED.with(|i| *i.borrow()),
+        "cookie_exceptions": COOKIE_EXCEPTIONS.with(|c| c.borrow().clone()),
+        "proxy_mode": PROXY_MODE.with(|p| p.borrow().clone()),
+        "proxy_http": PROXY_HTTP.with(|p| p.borrow().clone()),
+        "proxy_https": PROXY_HTTPS.with(|p| p.borrow().clone()),
+        "proxy_socks": PROXY_SOCKS.with(|p| p.borrow().clone()),
+        "proxy_ignore": PROXY_IGNORE.with(|p| p.borrow().clone()),
+        "proxy_pac": PROXY_PAC.with(|p| p.borrow().clone()),
+        "gpc": SEND_GPC.with(|g| *g.borrow()),
+        "dnt": SEND_DNT.with(|d| *d.borrow()),
+        "user_agent": CUSTOM_USER_AGENT.with(|u| u.borrow().clone()),
+        "site_user_agents": SITE_USER_AGENTS.with(|u| u.borrow().clone()),
+        "password_never_save": PASSWORD_NEVER_SAVE.with(|n| n.borrow().clone()),
+        "strip_tracking": STRIP_TRACKING.with(|s| *s.borrow()),
+        "tracking_params": TRACKING_PARAMS.with(|t| t.borrow().clone()),
+        "site_tracking_params": SITE_TRACKING_PARAMS.with(|t| t.borrow().clone()),
+        "allowed_schemes": ALLOWED_SCHEMES.with(|a| a.borrow().clone()),
+        "containers": CONTAINERS.with(|c| c.borrow().clone()),
+        "container_ids": CONTAINER_IDS.with(|c| c.borrow().clone()),
+        "container_sites": CONTAINER_SITES.with(|c| c.borrow().clone()),
+        "vertical_tabs": VERTICAL_TABS.with(|v| *v.borrow()),
+        "discard_after_minutes": DISCARD_AFTER_MINUTES.with(|d| *d.borrow()),
+        "discard_on_low_memory": DISCARD_ON_LOW_MEMORY.with(|d| *d.borrow()),
+        "sidebar_collapsed": SIDEBAR_COLLAPSED.with(|c| *c.borrow()),
+        "muted_sites": MUTED_SITES.with(|m| m.borrow().clone()),
+        "confirm_close": CONFIRM_CLOSE.with(|c| *c.borrow()),
+        "confirm_close_tabs": CONFIRM_CLOSE_TABS.with(|c| *c.borrow()),
+        "pinned_tabs": PINNED_TABS.with(|p| {
+            p.borrow().iter().filter(|t| !t.private).map(|t| (t.uri.clone(), t.container.clone())).collect::<Vec<_>>()
+        }),
+        "tab_groups": TAB_GROUPS.with(|g| g.borrow().iter().map(TabGroup::to_json).collect::<Vec<_>>()),
+        "saved_groups": SAVED_GROUPS.with(|g| g.borrow().iter().map(TabGroup::to_json).collect::<Vec<_>>()),
+    });
     let path = settings_path();
     if let Some(parent) = path.parent() { let _ = std::fs::create_dir_all(parent); }
-    if let (Ok(e), Ok(u)) = (serde_json::to_string(&engine), serde_json::to_string(&custom_url)) {
-        let _ = std::fs::write(path, format!(
-            "{{\"engine\":{},\"custom_url\":{},\"dark\":{},\"cache\":{}}}",
-            e, u, dark, cache
-        ));
-    }
+    let _ = std::fs::write(path, settings.to_string());
 }
 
 fn load_settings() {
@@ -166,8 +392,308 @@ fn load_settings() {
             if let Some(c) = v["cache"].as_bool() {
                 CACHE_ENABLED.with(|s| *s.borrow_mut() = c);
             }
+            if let Some(h) = v["https_only"].as_bool() {
+                HTTPS_ONLY.with(|s| *s.borrow_mut() = h);
+            }
+            if let Some(hosts) = v["https_exceptions"].as_array() {
+                let hosts = hosts.iter().filter_map(|h| h.as_str().map(str::to_string)).collect();
+                HTTPS_EXCEPTIONS.with(|s| *s.borrow_mut() = hosts);
+            }
+            if let Ok(certs) = serde_json::from_value::<Vec<(String, String)>>(v["tls_exceptions"].clone()) {
+                TLS_EXCEPTIONS.with(|s| *s.borrow_mut() = certs);
+            }
+            if let Ok(perms) = serde_json::from_value::<Vec<(String, String, bool)>>(v["permissions"].clone()) {
+                PERMISSIONS.with(|s| *s.borrow_mut() = perms);
+            }
+            if let Some(c) = v["cookie_policy"].as_str() {
+                COOKIE_POLICY.with(|s| *s.borrow_mut() = c.to_string());
+            }
+            if let Some(i) = v["itp"].as_bool() {
+                ITP_ENABLED.with(|s| *s.borrow_mut() = i);
+            }
+            if let Ok(rules) = serde_json::from_value::<Vec<(String, bool)>>(v["cookie_exceptions"].clone()) {
+                COOKIE_EXCEPTIONS.with(|s| *s.borrow_mut() = rules);
+            }
+            for (key, setting) in PROXY_FIELDS {
+                if let Some(value) = v[key].as_str() {
+                    setting.with(|s| *s.borrow_mut() = value.to_string());
+                }
+            }
+            PROXY_MODE.with(|p| {
+                let mut mode = p.borrow_mut();
+                if !PROXY_MODES.contains(&mode.as_str()) { *mode = "system".to_string(); }
+            });
+            if let Some(g) = v["gpc"].as_bool() {
+                SEND_GPC.with(|s| *s.borrow_mut() = g);
+            }
+            if let Some(d) = v["dnt"].as_bool() {
+                SEND_DNT.with(|s| *s.borrow_mut() = d);
+            }
+            if let Some(u) = v["user_agent"].as_str() {
+                CUSTOM_USER_AGENT.with(|s| *s.borrow_mut() = u.to_string());
+            }
+            if let Ok(sites) = serde_json::from_value::<Vec<(String, String)>>(v["site_user_agents"].clone()) {
+                SITE_USER_AGENTS.with(|s| *s.borrow_mut() = sites);
+            }
+            if let Ok(origins) = serde_json::from_value::<Vec<String>>(v["password_never_save"].clone()) {
+                PASSWORD_NEVER_SAVE.with(|s| *s.borrow_mut() = origins);
+            }
+            if let Some(strip) = v["strip_tracking"].as_bool() {
+                STRIP_TRACKING.with(|s| *s.borrow_mut() = strip);
+            }
+            if let Some(names) = v["tracking_params"].as_str() {
+                TRACKING_PARAMS.with(|s| *s.borrow_mut() = names.to_string());
+            }
+            if let Ok(sites) = serde_json::from_value::<Vec<(String, String)>>(v["site_tracking_params"].clone()) {
+                SITE_TRACKING_PARAMS.with(|s| *s.borrow_mut() = sites);
+            }
+            if let Ok(schemes) = serde_json::from_value::<Vec<String>>(v["allowed_schemes"].clone()) {
+                ALLOWED_SCHEMES.with(|s| *s.borrow_mut() = schemes);
+            }
+            if let Ok(containers) = serde_json::from_value::<Vec<(String, String)>>(v["containers"].clone()) {
+                CONTAINERS.with(|s| *s.borrow_mut() = containers);
+            }
+            if let Ok(ids) = serde_json::from_value(v["container_ids"].clone()) {
+                CONTAINER_IDS.with(|s| *s.borrow_mut() = ids);
+            }
+            // Containers from before ids keep the directory their name slugged to, first come first served
+            for (name, _) in CONTAINERS.with(|c| c.borrow().clone()) {
+                CONTAINER_IDS.with(|c| {
+                    let mut ids = c.borrow_mut();
+                    if ids.contains_key(&name) { return; }
+                    let slug = legacy_container_slug(&name);
+                    let id = if ids.values().any(|id| *id == slug) { glib::uuid_string_random().to_string() } else { slug };
+                    ids.insert(name, id);
+                });
+            }
+            if let Ok(sites) = serde_json::from_value::<Vec<(String, String)>>(v["container_sites"].clone()) {
+                CONTAINER_SITES.with(|s| *s.borrow_mut() = sites);
+            }
+            if let Some(vertical) = v["vertical_tabs"].as_bool() {
+                VERTICAL_TABS.with(|s| *s.borrow_mut() = vertical);
+            }
+            if let Some(minutes) = v["discard_after_minutes"].as_u64() {
+                DISCARD_AFTER_MINUTES.with(|s| *s.borrow_mut() = minutes as u32);
+            }
+            if let Some(low_memory) = v["discard_on_low_memory"].as_bool() {
+                DISCARD_ON_LOW_MEMORY.with(|s| *s.borrow_mut() = low_memory);
+            }
+            if let Some(collapsed) = v["sidebar_collapsed"].as_bool() {
+                SIDEBAR_COLLAPSED.with(|s| *s.borrow_mut() = collapsed);
+            }
+            if let Some(confirm) = v["confirm_close"].as_bool() {
+                CONFIRM_CLOSE.with(|s| *s.borrow_mut() = confirm);
+            }
+            if let Some(tabs) = v["confirm_close_tabs"].as_u64() {
+                CONFIRM_CLOSE_TABS.with(|s| *s.borrow_mut() = tabs as u32);
+            }
+            if let Ok(sites) = serde_json::from_value::<Vec<String>>(v["muted_sites"].clone()) {
+                MUTED_SITES.with(|s| *s.borrow_mut() = sites);
+            }
+            if let Ok(tabs) = serde_json::from_value::<Vec<(String, String)>>(v["pinned_tabs"].clone()) {
+                PINNED_TABS.with(|s| *s.borrow_mut() = tabs.into_iter().map(|(uri, container)| {
+                    SavedTab { view: glib::WeakRef::new(), uri, container, private: false }
+                }).collect());
+            }
+            for (key, groups) in [("tab_groups", &TAB_GROUPS), ("saved_groups", &SAVED_GROUPS)] {
+                if let Some(list) = v[key].as_array() {
+                    groups.with(|g| *g.borrow_mut() = list.iter().filter_map(TabGroup::from_json).collect());
+                }
+            }
+        }
+    }
+}
+
+const PROXY_FIELDS: [(&str, &std::thread::LocalKey<RefCell<String>>); 6] = [
+    ("proxy_mode", &PROXY_MODE),
+    ("proxy_http", &PROXY_HTTP),
+    ("proxy_https", &PROXY_HTTPS),
+    ("proxy_socks", &PROXY_SOCKS),
+    ("proxy_ignore", &PROXY_IGNORE),
+    ("proxy_pac", &PROXY_PAC),
+];
+
+const PROXY_MODES: [&str; 4] = ["system", "none", "manual", "pac"];
+
+// WebKit takes no PAC URL, so "pac" hands it to libproxy in the network process through its environment.
+// Runs from main() before GTK starts any thread, which is the only time setting variables is sound,
+// so a changed PAC URL applies from the next launch.
+fn export_pac_config() {
+    let Ok(content) = std::fs::read_to_string(settings_path()) else { return };
+    let Ok(v) = serde_json::from_str::<serde_json::Value>(&content) else { return };
+    let pac = v["proxy_pac"].as_str().unwrap_or_default().trim();
+    if v["proxy_mode"].as_str() != Some("pac") || pac.is_empty() { return; }
+    std::env::set_var("GIO_USE_PROXY_RESOLVER", "libproxy");
+    std::env::set_var("PX_FORCE_CONFIG", format!("pac+{}", pac));
+}
+
+fn proxy_uri(scheme: &str, value: &str) -> Option<String> {
+    let v = value.trim();
+    if v.is_empty() { return None; }
+    if v.contains("://") { Some(v.to_string()) } else { Some(format!("{}://{}", scheme, v)) }
+}
+
+fn apply_proxy_settings(ns: &webkit6::NetworkSession) {
+    match PROXY_MODE.with(|p| p.borrow().clone()).as_str() {
+        "none" => ns.set_proxy_settings(webkit6::NetworkProxyMode::NoProxy, None),
+        "manual" => {
+            let ignore = PROXY_IGNORE.with(|p| p.borrow().clone());
+            let ignore: Vec<&str> = ignore.split(',').map(str::trim).filter(|h| !h.is_empty()).collect();
+            let socks = proxy_uri("socks", &PROXY_SOCKS.with(|p| p.borrow().clone()));
+            let mut settings = webkit6::NetworkProxySettings::new(socks.as_deref(), &ignore);
+            if let Some(http) = proxy_uri("http", &PROXY_HTTP.with(|p| p.borrow().clone())) {
+                settings.add_proxy_for_scheme("http", &http);
+            }
+            if let Some(https) = proxy_uri("http", &PROXY_HTTPS.with(|p| p.borrow().clone())) {
+                settings.add_proxy_for_scheme("https", &https);
+            }
+            ns.set_proxy_settings(webkit6::NetworkProxyMode::Custom, Some(&settings));
         }
+        // PAC is resolved by libproxy inside the network process, see export_pac_config()
+        _ => ns.set_proxy_settings(webkit6::NetworkProxyMode::Default, None),
+    }
+}
+
+const USER_AGENT_PRESETS: [(&str, &str); 3] = [
+    ("firefox", "Mozilla/5.0 (X11; Linux x86_64; rv:131.0) Gecko/20100101 Firefox/131.0"),
+    ("chrome", "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/129.0.0.0 Safari/537.36"),
+    ("safari-mobile", "Mozilla/5.0 (iPhone; CPU iPhone OS 17_6 like Mac OS X) AppleWebKit/605.1.15 (KHTML, like Gecko) Version/17.6 Mobile/15E148 Safari/604.1"),
+];
+
+fn user_agent_for(uri: &str) -> Option<String> {
+    let site = uri_host(uri).and_then(|host| SITE_USER_AGENTS.with(|u| {
+        u.borrow().iter().find(|(domain, _)| host_matches(&host, domain)).map(|(_, preset)| preset.clone())
+    }));
+    if let Some(preset) = site {
+        return USER_AGENT_PRESETS.iter().find(|(name, _)| *name == preset).map(|(_, ua)| ua.to_string());
+    }
+    let custom = CUSTOM_USER_AGENT.with(|u| u.borrow().trim().to_string());
+    if custom.is_empty() { None } else { Some(custom) }
+}
+
+// Returns true when the view's user agent had to change for `uri`
+fn apply_user_agent(webview: &WebView, uri: &str) -> bool {
+    let Some(settings) = WebViewExt::settings(webview) else { return false };
+    let ua = user_agent_for(uri).unwrap_or_else(|| DEFAULT_USER_AGENT.with(|u| u.clone()));
+    if settings.user_agent().as_deref() == Some(ua.as_str()) { return false; }
+    settings.set_user_agent(Some(&ua));
+    true
+}
+
+const DEFAULT_TRACKING_PARAMS: &str = "utm_*, fbclid, gclid, dclid, msclkid, mc_eid, mc_cid, igshid, yclid, _hsenc, _hsmi";
+
+// Rules are comma separated parameter names; a trailing * matches any suffix
+fn tracking_param_matches(name: &str, rules: &str) -> bool {
+    rules.split(',').map(str::trim).filter(|r| !r.is_empty()).any(|rule| match rule.strip_suffix('*') {
+        Some(prefix) => name.starts_with(prefix),
+        None => name == rule,
+    })
+}
+
+fn clean_url(uri: &str) -> String {
+    let (rest, fragment) = match uri.split_once('#') {
+        Some((rest, fragment)) => (rest, Some(fragment)),
+        None => (uri, None),
+    };
+    let Some((base, query)) = rest.split_once('?') else { return uri.to_string() };
+    let host = uri_host(uri).unwrap_or_default();
+    let mut rules = vec![TRACKING_PARAMS.with(|t| t.borrow().clone())];
+    SITE_TRACKING_PARAMS.with(|t| {
+        rules.extend(t.borrow().iter().filter(|(domain, _)| host_matches(&host, domain)).map(|(_, names)| names.clone()));
+    });
+    let kept: Vec<&str> = query.split('&').filter(|pair| {
+        let name = url_decode(pair.split('=').next().unwrap_or_default());
+        !rules.iter().any(|r| tracking_param_matches(&name, r))
+    }).collect();
+    let mut clean = base.to_string();
+    if !kept.is_empty() {
+        clean.push('?');
+        clean.push_str(&kept.join("&"));
+    }
+    if let Some(fragment) = fragment {
+        clean.push('#');
+        clean.push_str(fragment);
+    }
+    clean
+}
+
+fn strip_tracking(uri: &str) -> String {
+    if STRIP_TRACKING.with(|s| *s.borrow()) { clean_url(uri) } else { uri.to_string() }
+}
+
+// The URI a navigation goes to once its tracking parameters are stripped
+fn navigation_uri(action: &mut webkit6::NavigationAction) -> Option<String> {
+    let uri = action.request()?.uri()?.to_string();
+    let clean = CLEAN_NAVIGATION.with(|c| c.borrow().as_ref().filter(|(tracked, _)| *tracked == uri).map(|(_, clean)| clean.clone()));
+    Some(clean.unwrap_or(uri))
+}
+
+// Reports links followed in the top frame; decide-policy can't tell a frame's navigations from the page's
+const TOP_LINK_SCRIPT: &str = "
+document.addEventListener('click', e => {
+  const link = e.target.closest && e.target.closest('a[href]');
+  if (link) window.webkit.messageHandlers.topLink.postMessage(link.href);
+}, true);
+";
+
+// Marks the page once a form field is edited, until a form is submitted
+const UNSAVED_FORM_SCRIPT: &str = "
+document.addEventListener('input', e => { if (e.target.form || e.target.isContentEditable) window.rugUnsavedForm = true; }, true);
+document.addEventListener('submit', () => { window.rugUnsavedForm = false; }, true);
+";
+
+fn install_user_scripts(webview: &WebView) {
+    let Some(ucm) = webview.user_content_manager() else { return };
+    ucm.remove_all_scripts();
+    let mut source = String::new();
+    if SEND_GPC.with(|g| *g.borrow()) {
+        source.push_str("Object.defineProperty(Navigator.prototype, 'globalPrivacyControl', {get: () => true, configurable: true});\n");
+    }
+    if SEND_DNT.with(|d| *d.borrow()) {
+        source.push_str("Object.defineProperty(Navigator.prototype, 'doNotTrack', {get: () => '1', configurable: true});\n");
+    }
+    if !source.is_empty() {
+        ucm.add_script(&webkit6::UserScript::new(
+            &source,
+            webkit6::UserContentInjectedFrames::AllFrames,
+            webkit6::UserScriptInjectionTime::Start,
+            &[],
+            &[],
+        ));
     }
+    ucm.add_script(&webkit6::UserScript::for_world(
+        passwords::FORM_SCRIPT,
+        webkit6::UserContentInjectedFrames::TopFrame,
+        webkit6::UserScriptInjectionTime::End,
+        "rug",
+        &["http://*/*", "https://*/*"],
+        &[],
+    ));
+    ucm.add_script(&webkit6::UserScript::for_world(
+        TOP_LINK_SCRIPT,
+        webkit6::UserContentInjectedFrames::TopFrame,
+        webkit6::UserScriptInjectionTime::Start,
+        "rug",
+        &["http://*/*", "https://*/*"],
+        &[],
+    ));
+    ucm.add_script(&webkit6::UserScript::for_world(
+        UNSAVED_FORM_SCRIPT,
+        webkit6::UserContentInjectedFrames::TopFrame,
+        webkit6::UserScriptInjectionTime::End,
+        "rug",
+        &["http://*/*", "https://*/*"],
+        &[],
+    ));
+}
+
+fn all_webviews() -> Vec<WebView> {
+    WINDOWS.with(|w| {
+        w.borrow().iter()
+            .flat_map(|bw| (0..bw.notebook.n_pages()).filter_map(move |i| bw.notebook.nth_page(Some(i))))
+            .filter_map(|page| page.downcast::<WebView>().ok())
+            .collect()
+    })
 }
 
 fn search_url(query: &str) -> String {
@@ -239,9 +765,13 @@ fn url_encode(s: &str) -> String {
     out
 }
 
-fn smart_uri(input: &str) -> String {
+fn smart_uri(input: &str, private: bool) -> String {
     let s = input.trim();
     if s.contains("://") {
+        return strip_tracking(s);
+    }
+    let scheme = if s.contains(' ') { None } else { typed_scheme(s) };
+    if scheme.as_ref().is_some_and(|scheme| gtk4::gio::AppInfo::default_for_uri_scheme(scheme).is_some()) {
         return s.to_string();
     }
     if s.contains(' ')
@@ -249,7 +779,495 @@ fn smart_uri(input: &str) -> String {
     {
         return search_url(s);
     }
-    format!("http://{}", s)
+    // Loaded as typed, so the missing application is reported instead of guessing a host
+    if scheme.is_some() {
+        return s.to_string();
+    }
+    let http = strip_tracking(&format!("http://{}", s));
+    https_upgrade(&http, private).unwrap_or(http)
+}
+
+fn uri_scheme(uri: &str) -> Option<String> {
+    let (scheme, _) = uri.split_once(':')?;
+    let mut chars = scheme.chars();
+    if !chars.next()?.is_ascii_alphabetic() { return None; }
+    if !chars.all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c)) { return None; }
+    Some(scheme.to_ascii_lowercase())
+}
+
+const WEB_SCHEMES: [&str; 9] = ["http", "https", "file", "about", "data", "blob", "javascript", "rug", "webkit-pdfjs-viewer"];
+
+// Schemes WebKit can't load itself and hands off to the desktop's default application
+fn external_scheme(uri: &str) -> Option<String> {
+    uri_scheme(uri).filter(|s| !WEB_SCHEMES.contains(&s.as_str()))
+}
+
+// Typed "host:port" reads like a scheme too, so a port after the colon rules one out
+fn typed_scheme(input: &str) -> Option<String> {
+    let scheme = external_scheme(input)?;
+    let port = input[scheme.len() + 1..].split(['/', '?', '#']).next().unwrap_or_default();
+    if !port.is_empty() && port.chars().all(|c| c.is_ascii_digit()) { return None; }
+    Some(scheme)
+}
+
+fn launch_external(app_info: &gtk4::gio::AppInfo, uri: &str, window: &ApplicationWindow) {
+    let ctx = WidgetExt::display(window).app_launch_context();
+    let _ = app_info.launch_uris(&[uri], Some(&ctx));
+}
+
+fn open_external(bw: &BrowserWindow, webview: &WebView, uri: &str, scheme: &str, redirect: bool) {
+    let Some(app_info) = gtk4::gio::AppInfo::default_for_uri_scheme(scheme) else {
+        let row = tab_prompt(bw, webview, &format!("No application is set up to open {}: links", scheme));
+        let dismiss = Button::with_label("Dismiss");
+        row.append(&dismiss);
+        dismiss.connect_clicked(clone!(#[weak] row, move |_| row.unparent()));
+        return;
+    };
+    // Servers don't get to launch apps on their own, even for allowed schemes
+    if !redirect && ALLOWED_SCHEMES.with(|a| a.borrow().iter().any(|s| s == scheme)) {
+        launch_external(&app_info, uri, &bw.window);
+        return;
+    }
+    let row = tab_prompt(bw, webview, &format!("Open this {}: link with {}?", scheme, app_info.display_name()));
+    let always = gtk4::CheckButton::with_label(&format!("Always allow {}: links", scheme));
+    let cancel = Button::with_label("Cancel");
+    let open = Button::with_label("Open");
+    row.append(&always);
+    row.append(&cancel);
+    row.append(&open);
+    cancel.connect_clicked(clone!(#[weak] row, move |_| row.unparent()));
+    let (uri, scheme) = (uri.to_string(), scheme.to_string());
+    open.connect_clicked(clone!(#[weak] row, #[weak] always, #[strong(rename_to = window)] bw.window, move |_| {
+        if always.is_active() {
+            ALLOWED_SCHEMES.with(|a| {
+                let mut schemes = a.borrow_mut();
+                if !schemes.contains(&scheme) { schemes.push(scheme.clone()); }
+            });
+            save_settings();
+        }
+        launch_external(&app_info, &uri, &window);
+        row.unparent();
+    }));
+}
+
+fn uri_host(url: &str) -> Option<String> {
+    let sep = url.find("://")?;
+    let after = &url[sep + 3..];
+    let authority = &after[..after.find(['/', '?', '#']).unwrap_or(after.len())];
+    let hostport = authority.rsplit_once('@').map(|(_, h)| h).unwrap_or(authority);
+    let host = match hostport.strip_prefix('[') {
+        Some(v6) => v6.split(']').next().unwrap_or(v6),
+        None => hostport.split(':').next().unwrap_or(hostport),
+    };
+    if host.is_empty() { return None; }
+    Some(host.to_lowercase())
+}
+
+fn https_error_uri(http_uri: &str) -> String {
+    format!("rug://https-error?url={}", url_encode(http_uri))
+}
+
+fn tls_error_messages(errors: gtk4::gio::TlsCertificateFlags) -> Vec<&'static str> {
+    use gtk4::gio::TlsCertificateFlags as F;
+    let known = [
+        (F::UNKNOWN_CA, "The certificate is not signed by a trusted authority."),
+        (F::BAD_IDENTITY, "The certificate does not match the site's name."),
+        (F::NOT_ACTIVATED, "The certificate is not valid yet."),
+        (F::EXPIRED, "The certificate has expired."),
+        (F::REVOKED, "The certificate has been revoked."),
+        (F::INSECURE, "The certificate uses an insecure algorithm."),
+        (F::GENERIC_ERROR, "The certificate could not be verified."),
+    ];
+    known.iter().filter(|(flag, _)| errors.contains(*flag)).map(|(_, msg)| *msg).collect()
+}
+
+fn certificate_chain(certificate: &gtk4::gio::TlsCertificate) -> Vec<html::CertificateDetails> {
+    let date = |cert: &gtk4::gio::TlsCertificate, prop: &str| {
+        cert.property::<Option<glib::DateTime>>(prop)
+            .and_then(|d| d.format("%Y-%m-%d %H:%M:%S UTC").ok())
+            .map(|d| d.to_string())
+            .unwrap_or_default()
+    };
+    let text = |cert: &gtk4::gio::TlsCertificate, prop: &str| {
+        cert.property::<Option<glib::GString>>(prop).map(|s| s.to_string()).unwrap_or_default()
+    };
+    let mut chain = Vec::new();
+    let mut next = Some(certificate.clone());
+    while let Some(cert) = next {
+        chain.push(html::CertificateDetails {
+            subject: text(&cert, "subject-name"),
+            issuer: text(&cert, "issuer-name"),
+            not_before: date(&cert, "not-valid-before"),
+            not_after: date(&cert, "not-valid-after"),
+            pem: text(&cert, "certificate-pem"),
+        });
+        next = cert.issuer();
+    }
+    chain
+}
+
+fn allow_saved_certificates(ns: &webkit6::NetworkSession) {
+    TLS_EXCEPTIONS.with(|t| {
+        for (host, pem) in t.borrow().iter() {
+            if let Ok(cert) = gtk4::gio::TlsCertificate::from_pem(pem) {
+                ns.allow_tls_certificate_for_host(&cert, host);
+            }
+        }
+    });
+}
+
+fn permission_kind(request: &webkit6::PermissionRequest) -> Option<(&'static str, &'static str)> {
+    if request.is::<webkit6::GeolocationPermissionRequest>() {
+        Some(("geolocation", "know your location"))
+    } else if request.is::<webkit6::NotificationPermissionRequest>() {
+        Some(("notifications", "show notifications"))
+    } else if let Some(media) = request.downcast_ref::<webkit6::UserMediaPermissionRequest>() {
+        if webkit6::functions::user_media_permission_is_for_display_device(media) {
+            Some(("screen", "share your screen"))
+        } else {
+            match (media.is_for_video_device(), media.is_for_audio_device()) {
+                (true, true) => Some(("camera-microphone", "use your camera and microphone")),
+                (true, false) => Some(("camera", "use your camera")),
+                _ => Some(("microphone", "use your microphone")),
+            }
+        }
+    } else if request.is::<webkit6::ClipboardPermissionRequest>() {
+        Some(("clipboard", "read your clipboard"))
+    } else if request.is::<webkit6::DeviceInfoPermissionRequest>() {
+        Some(("device-info", "see your cameras and microphones"))
+    } else if request.is::<webkit6::PointerLockPermissionRequest>() {
+        Some(("pointer-lock", "lock your mouse pointer"))
+    } else if request.is::<webkit6::MediaKeySystemPermissionRequest>() {
+        Some(("media-keys", "play protected content"))
+    } else if request.is::<webkit6::WebsiteDataAccessPermissionRequest>() {
+        Some(("storage-access", "use its cookies while you browse this site"))
+    } else {
+        None
+    }
+}
+
+fn permission_decision(origin: &str, kind: &str, private: bool) -> Option<bool> {
+    let find = |perms: &Vec<(String, String, bool)>| {
+        perms.iter().find(|(o, k, _)| o == origin && k == kind).map(|(_, _, allow)| *allow)
+    };
+    let session = if private { PRIVATE_PERMISSIONS.with(|p| find(&p.borrow())) } else { None };
+    session.or_else(|| PERMISSIONS.with(|p| find(&p.borrow())))
+}
+
+fn remember_permission(origin: &str, kind: &str, allow: bool, private: bool) {
+    let store = if private { &PRIVATE_PERMISSIONS } else { &PERMISSIONS };
+    store.with(|p| {
+        let mut perms = p.borrow_mut();
+        perms.retain(|(o, k, _)| !(o == origin && k == kind));
+        perms.push((origin.to_string(), kind.to_string(), allow));
+    });
+    if !private { save_settings(); }
+}
+
+fn sync_notification_permissions(ctx: &webkit6::WebContext) {
+    let (allowed, denied): (Vec<_>, Vec<_>) = PERMISSIONS.with(|p| {
+        p.borrow().iter()
+            .filter(|(_, k, _)| k == "notifications")
+            .map(|(o, _, allow)| (webkit6::SecurityOrigin::for_uri(o), *allow))
+            .partition(|(_, allow)| *allow)
+    });
+    let allowed: Vec<&webkit6::SecurityOrigin> = allowed.iter().map(|(o, _)| o).collect();
+    let denied: Vec<&webkit6::SecurityOrigin> = denied.iter().map(|(o, _)| o).collect();
+    ctx.initialize_notification_permissions(&allowed, &denied);
+}
+
+// A row in the window's prompt bar, shown only while its tab is the active one
+fn tab_prompt(bw: &BrowserWindow, webview: &WebView, text: &str) -> GtkBox {
+    let row = GtkBox::new(Orientation::Horizontal, 8);
+    row.add_css_class("permission-prompt");
+    row.set_margin_start(5);
+    row.set_margin_end(5);
+    row.set_margin_top(3);
+    row.set_margin_bottom(3);
+    let label = Label::new(Some(text));
+    label.set_hexpand(true);
+    label.set_halign(gtk4::Align::Start);
+    label.set_ellipsize(gtk4::pango::EllipsizeMode::Middle);
+    row.append(&label);
+    row.set_visible(is_active_tab(&bw.notebook, webview));
+    bw.permission_bar.append(&row);
+
+    // Prompts belong to their tab; drop them once that tab is gone
+    let switch_handler = bw.notebook.connect_switch_page(clone!(
+        #[weak] row, #[weak_allow_none] webview,
+        move |_, page, _| {
+            match &webview {
+                Some(wv) => row.set_visible(page == wv.upcast_ref::<gtk4::Widget>()),
+                None => row.unparent(),
+            }
+        }
+    ));
+    let switch_handler = RefCell::new(Some(switch_handler));
+    row.connect_destroy(clone!(#[strong(rename_to = notebook)] bw.notebook, move |_| {
+        if let Some(id) = switch_handler.take() { notebook.disconnect(id); }
+    }));
+    row
+}
+
+fn show_permission_prompt(bw: &BrowserWindow, webview: &WebView, request: &webkit6::PermissionRequest, origin: &str, kind: &'static str, what: &str) {
+    let row = tab_prompt(bw, webview, &format!("{} wants to {}", origin, what));
+    let remember = gtk4::CheckButton::with_label("Remember");
+    let deny = Button::with_label("Deny");
+    let allow = Button::with_label("Allow");
+    row.append(&remember);
+    row.append(&deny);
+    row.append(&allow);
+
+    let origin = origin.to_string();
+    let private = bw.private;
+    let decide = std::rc::Rc::new(clone!(
+        #[weak] row, #[weak] remember, #[strong] request,
+        move |allowed: bool| {
+            if allowed { request.allow(); } else { request.deny(); }
+            if remember.is_active() { remember_permission(&origin, kind, allowed, private); }
+            row.unparent();
+        }
+    ));
+    allow.connect_clicked(clone!(#[strong] decide, move |_| decide(true)));
+    deny.connect_clicked(move |_| decide(false));
+}
+
+// Runs `then(true)` once saved passwords are readable, asking for the master password if needed
+// Only called when the user asked to save or unlock; this is what may create the vault
+fn with_unlocked_passwords(window: &ApplicationWindow, then: impl FnOnce(bool) + 'static) {
+    let window = window.clone();
+    glib::spawn_future_local(async move {
+        if passwords::create_with_keyring().await {
+            then(true);
+        } else {
+            master_password_dialog(&window, then);
+        }
+    });
+}
+
+fn master_password_dialog(window: &ApplicationWindow, then: impl FnOnce(bool) + 'static) {
+    let creating = !passwords::vault_exists();
+    let dialog = gtk4::Window::builder()
+        .title(if creating { "Create Master Password" } else { "Unlock Saved Passwords" })
+        .transient_for(window)
+        .modal(true)
+        .resizable(false)
+        .default_width(360)
+        .build();
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    let intro = Label::new(Some(if creating {
+        "No system keyring is available. Choose a master password to encrypt saved passwords."
+    } else {
+        "Enter your master password to use saved passwords."
+    }));
+    intro.set_wrap(true);
+    intro.set_xalign(0.0);
+    let password = gtk4::PasswordEntry::builder().show_peek_icon(true).activates_default(true).placeholder_text("Master password").build();
+    let confirm = gtk4::PasswordEntry::builder().activates_default(true).placeholder_text("Confirm master password").visible(creating).build();
+    let error = Label::new(None);
+    error.add_css_class("error");
+    error.set_xalign(0.0);
+    let buttons = GtkBox::new(Orientation::Horizontal, 8);
+    buttons.set_halign(gtk4::Align::End);
+    let cancel = Button::with_label("Cancel");
+    let unlock = Button::with_label(if creating { "Create" } else { "Unlock" });
+    unlock.add_css_class("suggested-action");
+    buttons.append(&cancel);
+    buttons.append(&unlock);
+    content.append(&intro);
+    content.append(&password);
+    content.append(&confirm);
+    content.append(&error);
+    content.append(&buttons);
+    dialog.set_child(Some(&content));
+    dialog.set_default_widget(Some(&unlock));
+
+    let then = std::rc::Rc::new(RefCell::new(Some(then)));
+    unlock.connect_clicked(clone!(
+        #[weak] dialog, #[weak] password, #[weak] confirm, #[weak] error, #[strong] then,
+        move |_| {
+            let text = password.text();
+            if creating && text.is_empty() { error.set_text("Choose a master password"); return; }
+            if creating && text != confirm.text() { error.set_text("Passwords do not match"); return; }
+            match passwords::unlock_with_master_password(&text) {
+                Ok(()) => {
+                    let then = then.take();
+                    dialog.close();
+                    if let Some(then) = then { then(true); }
+                }
+                Err(message) => error.set_text(message),
+            }
+        }
+    ));
+    cancel.connect_clicked(clone!(#[weak] dialog, move |_| dialog.close()));
+    dialog.connect_close_request(move |_| {
+        if let Some(then) = then.take() { then(false); }
+        glib::Propagation::Proceed
+    });
+    dialog.present();
+}
+
+fn page_origin(webview: &WebView) -> Option<String> {
+    let uri = webview.uri()?;
+    if !uri.starts_with("https://") && !uri.starts_with("http://") { return None; }
+    Some(webkit6::SecurityOrigin::for_uri(&uri).to_str().to_string())
+}
+
+fn fill_saved_passwords() {
+    for wv in all_webviews() {
+        let Some(origin) = page_origin(&wv) else { continue };
+        let logins = passwords::logins_for(&origin);
+        if logins.is_empty() { continue; }
+        let script = format!("rugFill({})", serde_json::json!(logins));
+        wv.evaluate_javascript(&script, Some("rug"), None, gtk4::gio::Cancellable::NONE, |_| {});
+    }
+}
+
+// Handles passwords::FORM_SCRIPT; the origin comes from the view, never from the message
+fn password_message(bw: &BrowserWindow, webview: &WebView, message: &serde_json::Value, unlock_offered: &std::cell::Cell<bool>) -> serde_json::Value {
+    let Some(origin) = page_origin(webview) else { return serde_json::json!([]) };
+    match message["type"].as_str() {
+        Some("submit") => {
+            let username = message["username"].as_str().unwrap_or_default();
+            let password = message["password"].as_str().unwrap_or_default();
+            if !password.is_empty() { offer_save_password(bw, webview, &origin, username, password); }
+        }
+        Some("query") if passwords::is_unlocked() => {
+            return serde_json::json!(passwords::logins_for(&origin));
+        }
+        // Sent on the user's first click or key press in a credential field, so a page can't raise the keyring prompt by itself
+        Some("unlock") if passwords::may_have_saved(&origin) => {
+            if passwords::is_unlocked() {
+                return serde_json::json!(passwords::logins_for(&origin));
+            }
+            // An existing keyring vault opens without asking; the page is filled once it does
+            let offer = !unlock_offered.replace(true);
+            let (bw, webview) = (bw.clone(), webview.clone());
+            glib::spawn_future_local(async move {
+                if passwords::unlock_with_keyring().await {
+                    fill_saved_passwords();
+                } else if offer {
+                    offer_unlock_passwords(&bw, &webview, &origin);
+                }
+            });
+        }
+        _ => {}
+    }
+    serde_json::json!([])
+}
+
+fn offer_save_password(bw: &BrowserWindow, webview: &WebView, origin: &str, username: &str, password: &str) {
+    let never_save = if bw.private { &PRIVATE_NEVER_SAVE } else { &PASSWORD_NEVER_SAVE };
+    if [&PASSWORD_NEVER_SAVE, never_save].iter().any(|n| n.with(|n| n.borrow().iter().any(|o| o == origin))) { return; }
+    let saved = if passwords::is_unlocked() { passwords::logins_for(origin) } else { Vec::new() };
+    let text = match saved.iter().find(|(u, _)| u == username) {
+        Some((_, p)) if p == password => return,
+        Some(_) => format!("Update the saved password for {} on {}?", username, origin),
+        None if username.is_empty() => format!("Save password for {}?", origin),
+        None => format!("Save password for {} on {}?", username, origin),
+    };
+    let row = tab_prompt(bw, webview, &text);
+    let never = Button::with_label("Never for This Site");
+    let not_now = Button::with_label("Not Now");
+    let save = Button::with_label("Save");
+    row.append(&never);
+    row.append(&not_now);
+    row.append(&save);
+
+    let (origin, username, password) = (origin.to_string(), username.to_string(), password.to_string());
+    let private = bw.private;
+    never.connect_clicked(clone!(#[weak] row, #[strong] origin, move |_| {
+        never_save.with(|n| n.borrow_mut().push(origin.clone()));
+        if !private { save_settings(); }
+        row.unparent();
+    }));
+    not_now.connect_clicked(clone!(#[weak] row, move |_| row.unparent()));
+    save.connect_clicked(clone!(#[weak] row, #[strong(rename_to = window)] bw.window, move |_| {
+        let (origin, username, password) = (origin.clone(), username.clone(), password.clone());
+        with_unlocked_passwords(&window, move |unlocked| {
+            if unlocked { passwords::save(&origin, &username, &password); }
+        });
+        row.unparent();
+    }));
+}
+
+fn offer_unlock_passwords(bw: &BrowserWindow, webview: &WebView, origin: &str) {
+    let row = tab_prompt(bw, webview, &format!("Saved passwords for {} are locked", origin));
+    let not_now = Button::with_label("Not Now");
+    let unlock = Button::with_label("Unlock");
+    row.append(&not_now);
+    row.append(&unlock);
+    not_now.connect_clicked(clone!(#[weak] row, move |_| row.unparent()));
+    unlock.connect_clicked(clone!(#[weak] row, #[strong(rename_to = window)] bw.window, move |_| {
+        row.unparent();
+        with_unlocked_passwords(&window, |unlocked| {
+            if unlocked { fill_saved_passwords(); }
+        });
+    }));
+}
+
+fn host_matches(host: &str, domain: &str) -> bool {
+    host == domain || host.strip_suffix(domain).is_some_and(|sub| sub.ends_with('.'))
+}
+
+// The accept policy is per session, so site rules can't switch it per tab.
+// Instead blocked sites lose their cookies as soon as the session stores any, see setup_session.
+fn apply_cookie_policy(ns: &webkit6::NetworkSession) {
+    ns.set_itp_enabled(ITP_ENABLED.with(|i| *i.borrow()));
+    let any_allowed = COOKIE_EXCEPTIONS.with(|c| c.borrow().iter().any(|(_, allow)| *allow));
+    let policy = match COOKIE_POLICY.with(|c| c.borrow().clone()).as_str() {
+        "all" => webkit6::CookieAcceptPolicy::Always,
+        // Allowed sites still need their cookies, the rest get purged
+        "none" if !any_allowed => webkit6::CookieAcceptPolicy::Never,
+        _ => webkit6::CookieAcceptPolicy::NoThirdParty,
+    };
+    if let Some(cm) = ns.cookie_manager() {
+        cm.set_accept_policy(policy);
+    }
+}
+
+fn purge_blocked_cookies(ns: &webkit6::NetworkSession) {
+    let rules = COOKIE_EXCEPTIONS.with(|c| c.borrow().clone());
+    let block_unlisted = COOKIE_POLICY.with(|c| *c.borrow() == "none") && rules.iter().any(|(_, allow)| *allow);
+    if !block_unlisted && rules.iter().all(|(_, allow)| *allow) { return; }
+    let Some(wdm) = ns.website_data_manager() else { return };
+    let types = webkit6::WebsiteDataTypes::COOKIES;
+    wdm.fetch(types, gtk4::gio::Cancellable::NONE, clone!(#[weak] wdm, move |result| {
+        let Ok(records) = result else { return };
+        let rule = |domain: &str, allow: bool| rules.iter().any(|(h, a)| *a == allow && host_matches(h, domain));
+        let matching: Vec<&webkit6::WebsiteData> = records.iter()
+            .filter(|r| r.name().is_some_and(|n| rule(&n, false) || (block_unlisted && !rule(&n, true))))
+            .collect();
+        if !matching.is_empty() {
+            wdm.remove(types, &matching, gtk4::gio::Cancellable::NONE, |_| {});
+        }
+    }));
+}
+
+fn is_local_host(host: &str) -> bool {
+    if host == "localhost" || host.ends_with(".localhost") { return true; }
+    match host.parse::<std::net::IpAddr>() {
+        Ok(std::net::IpAddr::V4(ip)) => ip.is_private() || ip.is_loopback() || ip.is_link_local(),
+        Ok(std::net::IpAddr::V6(ip)) => {
+            let first = ip.segments()[0];
+            ip.is_loopback() || first & 0xfe00 == 0xfc00 || first & 0xffc0 == 0xfe80
+        }
+        Err(_) => false,
+    }
+}
+
+fn https_upgrade(uri: &str, private: bool) -> Option<String> {
+    if !HTTPS_ONLY.with(|h| *h.borrow()) { return None; }
+    let rest = uri.strip_prefix("http://")?;
+    let host = uri_host(uri)?;
+    if is_local_host(&host) { return None; }
+    if HTTPS_EXCEPTIONS.with(|e| e.borrow().contains(&host)) { return None; }
+    if private && PRIVATE_HTTPS_EXCEPTIONS.with(|e| e.borrow().contains(&host)) { return None; }
+    Some(format!("https://{}", rest))
 }
 
 fn top_domains(max: usize) -> Vec<(String, String)> {
@@ -269,7 +1287,7 @@ fn top_domains(max: usize) -> Vec<(String, String)> {
     let mut v: Vec<(String, String, usize)> = counts.into_iter()
         .map(|(host, (root, n))| (host, root, n))
         .collect();
-    v.sort_by(|a, b| b.2.cmp(&a.2));
+    v.sort_by_key(|(_, _, n)| std::cmp::Reverse(*n));
     v.into_iter().take(max).map(|(host, root, _)| (root, host)).collect()
 }
 
@@ -285,6 +1303,25 @@ fn search_history(query: &str, max: usize) -> Vec<(String, String, Option<String
     })
 }
 
+// The best history entry that starts with what was typed, cut at the end of its host until the
+// typed text reaches the path; `None` for anything that looks like a search
+fn inline_completion(typed: &str) -> Option<String> {
+    if typed.is_empty() || typed.contains(char::is_whitespace) { return None; }
+    search_history(typed, usize::MAX).into_iter().find_map(|(url, _, _)| {
+        let no_scheme = url.split_once("://").map_or(url.as_str(), |(_, rest)| rest);
+        let bare = no_scheme.strip_prefix("www.").unwrap_or(no_scheme);
+        let form = [url.as_str(), no_scheme, bare].into_iter()
+            .find(|f| f.get(..typed.len()).is_some_and(|prefix| prefix.eq_ignore_ascii_case(typed)))?;
+        let host_start = form.find("://").map_or(0, |i| i + 3);
+        let completion = match form[host_start..].find('/') {
+            Some(slash) if !typed.get(host_start..).unwrap_or_default().contains('/') => &form[..host_start + slash],
+            _ => form,
+        };
+        let rest = completion.get(typed.len()..).filter(|rest| !rest.is_empty())?;
+        Some(format!("{}{}", typed, rest))
+    })
+}
+
 fn default_favicon() -> gtk4::gdk::Texture {
     const S: usize = 16;
     let mut px = vec![0u8; S * S * 4];
@@ -349,78 +1386,525 @@ fn current_webview(notebook: &Notebook) -> Option<WebView> {
         .and_then(|w| w.downcast::<WebView>().ok())
 }
 
-fn add_tab(
-    notebook: &Notebook,
-    window: &ApplicationWindow,
-    url_bar: &Entry,
-    back_button: &Button,
-    forward_button: &Button,
-    progress_bar: &ProgressBar,
-    app: &Application,
-    related_view: Option<&WebView>,
-    initial_uri: Option<&str>,
-) -> WebView {
-    let webview = match related_view {
-        Some(rv) => webkit6::WebView::builder().related_view(rv).build(),
-        None => NETWORK_SESSION.with(|s| match s.borrow().as_ref() {
-            Some(ns) => webkit6::WebView::builder().network_session(ns).build(),
-            None => WebView::new(),
-        }),
-    };
+#[derive(Clone)]
+struct BrowserWindow {
+    id: String,
+    app: Application,
+    window: ApplicationWindow,
+    notebook: Notebook,
+    url_bar: Entry,
+    back_button: Button,
+    forward_button: Button,
+    progress_bar: ProgressBar,
+    permission_bar: GtkBox,
+    tab_sidebar: GtkBox,
+    tab_list: ListBox,
+    sidebar_collapse: gtk4::ToggleButton,
+    audio_button: gtk4::MenuButton,
+    find_bar: gtk4::SearchBar,
+    find_entry: gtk4::SearchEntry,
+    closing: std::rc::Rc<std::cell::Cell<bool>>,
+    url_box: GtkBox,
+    was_fullscreen: std::rc::Rc<std::cell::Cell<bool>>,
+    private: bool,
+}
 
-    match initial_uri {
-        Some(uri) => webview.load_uri(uri),
-        None if related_view.is_none() => webview.load_uri("rug://home"),
-        _ => {}
+fn setup_session(ns: &webkit6::NetworkSession, app: &Application) {
+    if let Some(wdm) = ns.website_data_manager() {
+        wdm.set_favicons_enabled(true);
     }
+    allow_saved_certificates(ns);
+    apply_proxy_settings(ns);
+    apply_cookie_policy(ns);
+    if let Some(cm) = ns.cookie_manager() {
+        cm.connect_changed(clone!(#[weak] ns, move |_| purge_blocked_cookies(&ns)));
+    }
+    ns.connect_download_started(clone!(#[strong] app, move |_, download| {
+        ACTIVE_DOWNLOADS.with(|d| *d.borrow_mut() += 1);
+        // Failed and cancelled downloads finish too
+        download.connect_finished(|_| ACTIVE_DOWNLOADS.with(|d| {
+            let mut active = d.borrow_mut();
+            *active = active.saturating_sub(1);
+        }));
+        download.connect_decide_destination(clone!(#[strong] app, move |download, suggested_filename| {
+            let download = download.clone();
+            let dialog = gtk4::FileDialog::new();
+            dialog.set_initial_name(Some(suggested_filename));
+            let downloads_folder = gtk4::gio::File::for_path(glib::home_dir().join("Downloads"));
+            dialog.set_initial_folder(Some(&downloads_folder));
+            let window = app.active_window();
+            dialog.save(window.as_ref(), gtk4::gio::Cancellable::NONE, move |result| {
+                match result {
+                    Ok(file) => {
+                        if let Some(path) = file.path() {
+                            download.set_destination(&path.to_string_lossy());
+                        } else {
+                            download.cancel();
+                        }
+                    }
+                    Err(_) => { download.cancel(); }
+                }
+            });
+            true
+        }));
+    }));
+}
 
-    webview.set_hexpand(true);
-    webview.set_vexpand(true);
-
-    webview.connect_load_changed(clone!(
-        #[weak] notebook, #[weak] back_button, #[weak] forward_button,
-        #[weak] webview, #[weak] url_bar, #[weak] progress_bar,
-        move |_, load_event| {
-            if load_event == webkit6::LoadEvent::Finished {
-                let uri = webview.uri().unwrap_or_default();
-                let title = webview.title().unwrap_or_default();
-                update_recent(&uri, &title);
-            }
-            if !is_active_tab(&notebook, &webview) { return; }
-            back_button.set_sensitive(webview.can_go_back());
-            forward_button.set_sensitive(webview.can_go_forward());
-            url_bar.set_text(&webview.uri().unwrap_or_default());
-            if load_event == webkit6::LoadEvent::Started {
-                webview.grab_focus();
+const DEFAULT_CONTAINERS: [(&str, &str); 4] = [
+    ("Personal", "#3b82f6"),
+    ("Work", "#f97316"),
+    ("Banking", "#22c55e"),
+    ("Shopping", "#ec4899"),
+];
+
+fn valid_color(color: &str) -> bool {
+    color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn container_color(name: &str) -> Option<String> {
+    CONTAINERS.with(|c| c.borrow().iter().find(|(n, _)| n == name).map(|(_, color)| color.clone()))
+}
+
+fn assigned_container(host: &str) -> Option<String> {
+    CONTAINER_SITES.with(|c| {
+        c.borrow().iter().find(|(domain, _)| host_matches(host, domain)).map(|(_, name)| name.clone())
+    }).filter(|name| container_color(name).is_some())
+}
+
+fn container_of(webview: &WebView) -> Option<String> {
+    let ns = webview.network_session()?;
+    CONTAINER_SESSIONS.with(|c| c.borrow().iter().find(|(_, s)| **s == ns).map(|(name, _)| name.clone()))
+}
+
+// Each container keeps its own persistent cookie jar and storage, opened on first use
+fn container_session(app: &Application, name: &str) -> Option<webkit6::NetworkSession> {
+    container_color(name)?;
+    Some(CONTAINER_SESSIONS.with(|c| {
+        c.borrow_mut().entry(name.to_string()).or_insert_with(|| {
+            let (data_dir, cache_dir) = container_dirs(name);
+            let ns = webkit6::NetworkSession::new(
+                Some(&data_dir.to_string_lossy()),
+                Some(&cache_dir.to_string_lossy()),
+            );
+            setup_session(&ns, app);
+            if let Some(cm) = ns.cookie_manager() {
+                cm.set_persistent_storage(
+                    &data_dir.join("cookies.sqlite").to_string_lossy(),
+                    webkit6::CookiePersistentStorage::Sqlite,
+                );
             }
-            if load_event == webkit6::LoadEvent::Finished {
-                progress_bar.set_fraction(0.0);
+            ns
+        }).clone()
+    }))
+}
+
+fn container_stripe(color: &str) -> gtk4::DrawingArea {
+    let stripe = gtk4::DrawingArea::new();
+    stripe.set_content_width(3);
+    let rgba = gtk4::gdk::RGBA::parse(color).unwrap_or(gtk4::gdk::RGBA::BLACK);
+    stripe.set_draw_func(move |_, cr, width, height| {
+        cr.set_source_rgb(rgba.red().into(), rgba.green().into(), rgba.blue().into());
+        cr.rectangle(0.0, 0.0, width.into(), height.into());
+        let _ = cr.fill();
+    });
+    stripe
+}
+
+fn all_sessions() -> Vec<webkit6::NetworkSession> {
+    let mut sessions: Vec<webkit6::NetworkSession> = CONTAINER_SESSIONS.with(|c| c.borrow().values().cloned().collect());
+    for session in [&NETWORK_SESSION, &PRIVATE_SESSION] {
+        session.with(|s| sessions.extend(s.borrow().clone()));
+    }
+    sessions
+}
+
+// Private windows share one ephemeral session, created on first use
+fn private_session(app: &Application) -> webkit6::NetworkSession {
+    PRIVATE_SESSION.with(|s| {
+        s.borrow_mut().get_or_insert_with(|| {
+            let ns = webkit6::NetworkSession::new_ephemeral();
+            setup_session(&ns, app);
+            ns
+        }).clone()
+    })
+}
+
+// Pinned and grouped tabs outlive their window: entries whose view is gone are reopened next launch
+struct SavedTab {
+    view: glib::WeakRef<WebView>,
+    uri: String,
+    container: String,
+    private: bool,
+}
+
+impl SavedTab {
+    fn new(webview: &WebView) -> SavedTab {
+        let view = glib::WeakRef::new();
+        view.set(Some(webview));
+        SavedTab {
+            view,
+            uri: webview.uri().unwrap_or_default().to_string(),
+            container: container_of(webview).unwrap_or_default(),
+            private: is_private(webview),
+        }
+    }
+
+    fn is(&self, webview: &WebView) -> bool {
+        self.view.upgrade().as_ref() == Some(webview)
+    }
+}
+
+// Keeps the URI reopened for a pinned or grouped tab in step with where it has navigated
+fn remember_tab_uri(webview: &WebView) {
+    let uri = webview.uri().unwrap_or_default().to_string();
+    let mut changed = false;
+    let mut update = |tab: &mut SavedTab| {
+        if tab.is(webview) && tab.uri != uri {
+            tab.uri = uri.clone();
+            changed = true;
+        }
+    };
+    PINNED_TABS.with(|p| p.borrow_mut().iter_mut().for_each(&mut update));
+    TAB_GROUPS.with(|g| g.borrow_mut().iter_mut().flat_map(|g| g.tabs.iter_mut()).for_each(&mut update));
+    if changed && !is_private(webview) { save_settings(); }
+}
+
+fn is_pinned(webview: &WebView) -> bool {
+    PINNED_TABS.with(|p| p.borrow().iter().any(|t| t.is(webview)))
+}
+
+fn pinned_count(notebook: &Notebook) -> u32 {
+    (0..notebook.n_pages())
+        .filter_map(|i| notebook.nth_page(Some(i)).and_then(|p| p.downcast::<WebView>().ok()))
+        .filter(is_pinned)
+        .count() as u32
+}
+
+fn set_pinned(notebook: &Notebook, webview: &WebView, pinned: bool) {
+    if pinned == is_pinned(webview) { return; }
+    if pinned {
+        remove_from_group(notebook, webview);
+        let position = pinned_count(notebook);
+        PINNED_TABS.with(|p| p.borrow_mut().push(SavedTab::new(webview)));
+        notebook.reorder_child(webview, Some(position));
+    } else {
+        PINNED_TABS.with(|p| p.borrow_mut().retain(|t| !t.is(webview)));
+        notebook.reorder_child(webview, Some(pinned_count(notebook)));
+    }
+    if !is_private(webview) { save_settings(); }
+}
+
+// Reopens the tabs pinned last session at the left of the first window
+fn restore_pinned_tabs(bw: &BrowserWindow) {
+    let saved: Vec<(String, String)> = PINNED_TABS.with(|p| {
+        p.borrow_mut().drain(..).map(|t| (t.uri, t.container)).collect()
+    });
+    let current = current_webview(&bw.notebook);
+    for (uri, container) in saved.iter().filter(|(uri, _)| !uri.is_empty()) {
+        let container = Some(container.as_str()).filter(|c| !c.is_empty());
+        let webview = add_tab(bw, None, Some(uri), container, true);
+        set_pinned(&bw.notebook, &webview, true);
+    }
+    refresh_groups(&bw.notebook);
+    if let Some(n) = current.and_then(|wv| bw.notebook.page_num(&wv)) {
+        bw.notebook.set_current_page(Some(n));
+    }
+}
+
+const GROUP_COLORS: [(&str, &str); 8] = [
+    ("Grey", "#6b7280"),
+    ("Blue", "#3b82f6"),
+    ("Red", "#ef4444"),
+    ("Yellow", "#eab308"),
+    ("Green", "#22c55e"),
+    ("Pink", "#ec4899"),
+    ("Purple", "#a855f7"),
+    ("Cyan", "#06b6d4"),
+];
+
+// Group members sit next to each other in the tab strip; `window` ties a group to the
+// window it was made in so each window's groups are reopened together
+struct TabGroup {
+    id: u32,
+    name: String,
+    color: String,
+    collapsed: bool,
+    window: String,
+    tabs: Vec<SavedTab>,
+}
+
+impl TabGroup {
+    fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "name": self.name,
+            "color": self.color,
+            "collapsed": self.collapsed,
+            "window": self.window,
+            "tabs": self.tabs.iter().map(|t| (t.uri.clone(), t.container.clone())).collect::<Vec<_>>(),
+        })
+    }
+
+    fn from_json(v: &serde_json::Value) -> Option<TabGroup> {
+        let tabs = serde_json::from_value::<Vec<(String, String)>>(v["tabs"].clone()).ok()?;
+        Some(TabGroup {
+            id: next_group_id(),
+            name: v["name"].as_str()?.to_string(),
+            color: v["color"].as_str().filter(|c| valid_color(c)).unwrap_or(GROUP_COLORS[0].1).to_string(),
+            collapsed: v["collapsed"].as_bool().unwrap_or(false),
+            window: v["window"].as_str().unwrap_or_default().to_string(),
+            tabs: tabs.into_iter().map(|(uri, container)| SavedTab { view: glib::WeakRef::new(), uri, container, private: false }).collect(),
+        })
+    }
+}
+
+fn next_group_id() -> u32 {
+    NEXT_GROUP_ID.with(|n| {
+        let id = *n.borrow();
+        *n.borrow_mut() += 1;
+        id
+    })
+}
+
+fn group_of(webview: &WebView) -> Option<u32> {
+    TAB_GROUPS.with(|g| g.borrow().iter().find(|g| g.tabs.iter().any(|t| t.is(webview))).map(|g| g.id))
+}
+
+fn with_group<R>(id: u32, f: impl FnOnce(&mut TabGroup) -> R) -> Option<R> {
+    TAB_GROUPS.with(|g| g.borrow_mut().iter_mut().find(|g| g.id == id).map(f))
+}
+
+fn notebook_tabs(notebook: &Notebook) -> Vec<WebView> {
+    (0..notebook.n_pages())
+        .filter_map(|i| notebook.nth_page(Some(i)).and_then(|p| p.downcast::<WebView>().ok()))
+        .collect()
+}
+
+fn group_members(notebook: &Notebook, id: u32) -> Vec<WebView> {
+    notebook_tabs(notebook).into_iter().filter(|wv| group_of(wv) == Some(id)).collect()
+}
+
+fn window_groups(bw: &BrowserWindow) -> Vec<(u32, String)> {
+    TAB_GROUPS.with(|g| g.borrow().iter().filter(|g| g.window == bw.id).map(|g| (g.id, g.name.clone())).collect())
+}
+
+fn create_group(bw: &BrowserWindow, name: &str, color: Option<&str>) -> u32 {
+    let used: Vec<String> = TAB_GROUPS.with(|g| g.borrow().iter().filter(|g| g.window == bw.id).map(|g| g.color.clone()).collect());
+    let color = color.map(str::to_string).unwrap_or_else(|| {
+        GROUP_COLORS.iter().map(|(_, c)| c.to_string()).find(|c| !used.contains(c)).unwrap_or(GROUP_COLORS[0].1.to_string())
+    });
+    let id = next_group_id();
+    let name = if name.is_empty() { format!("Group {}", window_groups(bw).len() + 1) } else { name.to_string() };
+    TAB_GROUPS.with(|g| g.borrow_mut().push(TabGroup { id, name, color, collapsed: false, window: bw.id.clone(), tabs: Vec::new() }));
+    id
+}
+
+// Moves each tab to the end of the group so members stay side by side
+fn add_to_group(notebook: &Notebook, id: u32, webviews: &[WebView]) {
+    for webview in webviews.iter().filter(|wv| !is_pinned(wv)) {
+        if group_of(webview) == Some(id) { continue; }
+        TAB_GROUPS.with(|g| g.borrow_mut().iter_mut().for_each(|g| g.tabs.retain(|t| !t.is(webview))));
+        let last = group_members(notebook, id).last().and_then(|wv| notebook.page_num(wv));
+        with_group(id, |g| g.tabs.push(SavedTab::new(webview)));
+        if let (Some(last), Some(current)) = (last, notebook.page_num(webview)) {
+            notebook.reorder_child(webview, Some(if current < last { last } else { last + 1 }));
+        }
+    }
+    TAB_GROUPS.with(|g| g.borrow_mut().retain(|g| !g.tabs.is_empty()));
+    refresh_groups(notebook);
+    save_settings();
+}
+
+fn remove_from_group(notebook: &Notebook, webview: &WebView) {
+    let Some(id) = group_of(webview) else { return };
+    let members = group_members(notebook, id);
+    with_group(id, |g| g.tabs.retain(|t| !t.is(webview)));
+    TAB_GROUPS.with(|g| g.borrow_mut().retain(|g| !g.tabs.is_empty()));
+    // Step out past the rest of the group rather than splitting it
+    if let Some(last) = members.iter().rfind(|wv| *wv != webview).and_then(|wv| notebook.page_num(wv)) {
+        if notebook.page_num(webview).is_some_and(|current| current < last) {
+            notebook.reorder_child(webview, Some(last));
+        }
+    }
+    webview.set_visible(true);
+    refresh_groups(notebook);
+    save_settings();
+}
+
+// A dragged tab joins the group it lands inside and leaves the group it was dragged out of
+fn regroup_after_move(notebook: &Notebook, webview: &WebView, index: u32) {
+    let neighbour = |i: Option<u32>| i.and_then(|i| notebook.nth_page(Some(i))).and_then(|p| p.downcast::<WebView>().ok()).and_then(|wv| group_of(&wv));
+    let (left, right) = (neighbour(index.checked_sub(1)), neighbour(Some(index + 1)));
+    let own = group_of(webview);
+    if left.is_some() && left == right && own != left {
+        if let Some(id) = left { add_to_group(notebook, id, std::slice::from_ref(webview)); }
+    } else if let Some(id) = own.filter(|id| left != Some(*id) && right != Some(*id)) {
+        if group_members(notebook, id).len() > 1 {
+            with_group(id, |g| g.tabs.retain(|t| !t.is(webview)));
+            refresh_groups(notebook);
+            save_settings();
+        }
+    }
+}
+
+// Collapsed groups keep only their first tab, which shows the group chip
+fn refresh_groups(notebook: &Notebook) {
+    let current = current_webview(notebook);
+    for webview in notebook_tabs(notebook) {
+        let first = group_of(&webview).and_then(|id| group_members(notebook, id).first().cloned());
+        let collapsed = group_of(&webview).and_then(|id| with_group(id, |g| g.collapsed)).unwrap_or(false);
+        let hidden = collapsed && first.as_ref() != Some(&webview);
+        if hidden && current.as_ref() == Some(&webview) {
+            if let Some(n) = first.as_ref().and_then(|wv| notebook.page_num(wv)) { notebook.set_current_page(Some(n)); }
+        }
+        webview.set_visible(!hidden);
+        if let Some(label) = notebook.tab_label(&webview) {
+            let _ = label.activate_action("tabctx.refresh-group", None);
+        }
+    }
+}
+
+fn set_group_collapsed(notebook: &Notebook, id: u32, collapsed: bool) {
+    with_group(id, |g| g.collapsed = collapsed);
+    refresh_groups(notebook);
+    save_settings();
+}
+
+fn close_group(bw: &BrowserWindow, id: u32) {
+    let members = group_members(&bw.notebook, id);
+    TAB_GROUPS.with(|g| g.borrow_mut().retain(|g| g.id != id));
+    for webview in members {
+        close_tab(&webview);
+    }
+    save_settings();
+}
+
+// Saved groups are keyed by id, so saving a group never replaces another with the same name
+fn save_group(id: u32) {
+    let Some(copy) = with_group(id, |g| TabGroup {
+        id: next_group_id(),
+        name: g.name.clone(),
+        color: g.color.clone(),
+        collapsed: false,
+        window: String::new(),
+        tabs: g.tabs.iter().map(|t| SavedTab { view: glib::WeakRef::new(), uri: t.uri.clone(), container: t.container.clone(), private: false }).collect(),
+    }) else { return };
+    SAVED_GROUPS.with(|s| s.borrow_mut().push(copy));
+    save_settings();
+}
+
+fn open_group(bw: &BrowserWindow, name: &str, color: &str, collapsed: bool, tabs: &[(String, String)]) {
+    let webviews: Vec<WebView> = tabs.iter().filter(|(uri, _)| !uri.is_empty()).map(|(uri, container)| {
+        add_tab(bw, None, Some(uri), Some(container.as_str()).filter(|c| !c.is_empty()), true)
+    }).collect();
+    if webviews.is_empty() { return; }
+    let id = create_group(bw, name, Some(color));
+    add_to_group(&bw.notebook, id, &webviews);
+    if collapsed { set_group_collapsed(&bw.notebook, id, true); }
+}
+
+// Reopens last session's groups, each window's groups together in a window of their own
+fn restore_tab_groups(bw: &BrowserWindow) {
+    let groups: Vec<TabGroup> = TAB_GROUPS.with(|g| g.borrow_mut().drain(..).collect());
+    let current = current_webview(&bw.notebook);
+    let mut windows: Vec<(String, BrowserWindow)> = Vec::new();
+    for group in groups {
+        let target = match windows.iter().find(|(id, _)| *id == group.window) {
+            Some((_, target)) => target.clone(),
+            None => {
+                let target = if windows.is_empty() { bw.clone() } else { browser_window(&bw.app, false) };
+                windows.push((group.window.clone(), target.clone()));
+                target
             }
+        };
+        let tabs: Vec<(String, String)> = group.tabs.into_iter().map(|t| (t.uri, t.container)).collect();
+        open_group(&target, &group.name, &group.color, group.collapsed, &tabs);
+    }
+    if let Some(n) = current.and_then(|wv| bw.notebook.page_num(&wv)) {
+        bw.notebook.set_current_page(Some(n));
+    }
+}
+
+fn add_tab(bw: &BrowserWindow, related_view: Option<&WebView>, initial_uri: Option<&str>, container: Option<&str>, select: bool) -> WebView {
+    // Tabs can move between windows, so handlers look up the window a tab is in when they run
+    let BrowserWindow { app, notebook, private, .. } = bw;
+    let private = *private;
+    let container = match related_view {
+        Some(rv) => container_of(rv),
+        None if private => None,
+        None => container.map(str::to_string)
+            .or_else(|| initial_uri.and_then(uri_host).and_then(|host| assigned_container(&host))),
+    };
+    let webview = match related_view {
+        // Related views share their opener's settings unless given their own
+        Some(rv) => webkit6::WebView::builder().related_view(rv).settings(&webkit6::Settings::new()).build(),
+        None if private => webkit6::WebView::builder().network_session(&private_session(app)).build(),
+        None => match container.as_deref().and_then(|c| container_session(app, c)) {
+            Some(ns) => webkit6::WebView::builder().network_session(&ns).build(),
+            None => NETWORK_SESSION.with(|s| match s.borrow().as_ref() {
+                Some(ns) => webkit6::WebView::builder().network_session(ns).build(),
+                None => WebView::new(),
+            }),
+        },
+    };
+
+    if let Some(rv) = related_view { note_shared_process(rv, &webview); }
+    install_user_scripts(&webview);
+
+    // An empty `initial_uri` leaves the view blank for a caller that restores a session state into it
+    match initial_uri {
+        Some("") => {}
+        Some(uri) => webview.load_uri(uri),
+        None if related_view.is_none() => webview.load_uri("rug://home"),
+        _ => {}
+    }
+
+    webview.set_hexpand(true);
+    webview.set_vexpand(true);
+
+    webview.connect_load_changed(move |webview, load_event| {
+        if load_event == webkit6::LoadEvent::Finished && !private {
+            let uri = webview.uri().unwrap_or_default();
+            let title = webview.title().unwrap_or_default();
+            update_recent(&uri, &title);
         }
-    ));
+        let Some(BrowserWindow { notebook, url_bar, back_button, forward_button, progress_bar, .. }) = window_of(webview) else { return };
+        if !is_active_tab(&notebook, webview) { return; }
+        back_button.set_sensitive(webview.can_go_back());
+        forward_button.set_sensitive(webview.can_go_forward());
+        url_bar.set_text(&webview.uri().unwrap_or_default());
+        if load_event == webkit6::LoadEvent::Started {
+            webview.grab_focus();
+        }
+        if load_event == webkit6::LoadEvent::Finished {
+            progress_bar.set_fraction(0.0);
+        }
+    });
 
-    webview.connect_notify_local(
-        Some("estimated-load-progress"),
-        clone!(#[weak] notebook, #[weak] progress_bar, #[weak] webview, move |_, _| {
-            if !is_active_tab(&notebook, &webview) { return; }
-            progress_bar.set_fraction(webview.estimated_load_progress());
-        }),
-    );
+    if let Some(fc) = webview.find_controller() {
+        fc.connect_found_text(|fc, _| mark_find_result(fc, true));
+        fc.connect_failed_to_find_text(|fc| mark_find_result(fc, false));
+    }
+
+    webview.connect_estimated_load_progress_notify(|webview| {
+        let Some(bw) = window_of(webview) else { return };
+        if !is_active_tab(&bw.notebook, webview) { return; }
+        bw.progress_bar.set_fraction(webview.estimated_load_progress());
+    });
 
-    webview.connect_context_menu(clone!(
-        #[strong] notebook, #[strong] window, #[strong] url_bar,
-        #[strong] back_button, #[strong] forward_button, #[strong] progress_bar, #[strong] app,
-        move |_, menu, hit_test| {
+    webview.connect_context_menu(
+        move |webview, menu, hit_test| {
+            if is_kiosk() { return true; }
+            let Some(bw) = window_of(webview) else { return false };
+            // Links opened from a container tab stay in that container
+            let container = container_of(webview);
             if hit_test.context_is_link() {
                 if let Some(link_uri) = hit_test.link_uri() {
                     let uri = link_uri.to_string();
                     let action = gtk4::gio::SimpleAction::new("open-link-in-new-tab", None);
                     action.connect_activate(clone!(
-                        #[weak] notebook, #[weak] window, #[weak] url_bar,
-                        #[weak] back_button, #[weak] forward_button, #[weak] progress_bar, #[strong] app,
+                        #[strong] bw, #[strong] uri, #[strong] container,
                         move |_, _| {
-                            add_tab(&notebook, &window, &url_bar, &back_button,
-                                    &forward_button, &progress_bar, &app, None, Some(&uri));
+                            add_tab(&bw, None, Some(&uri), container.as_deref(), true);
                         }
                     ));
                     let items = menu.items();
@@ -434,6 +1918,57 @@ fn add_tab(
                         Some(p) => menu.insert(&new_tab_item, p as i32),
                         None => menu.append(&new_tab_item),
                     }
+
+                    let private_action = gtk4::gio::SimpleAction::new("open-link-in-private-window", None);
+                    private_action.connect_activate(clone!(
+                        #[strong] bw, #[strong] uri,
+                        move |_, _| {
+                            create_browser_window(&bw.app, None, true).load_uri(&uri);
+                        }
+                    ));
+                    let private_item = webkit6::ContextMenuItem::from_gaction(
+                        &private_action, "Open Link in Private Window", None
+                    );
+                    match pos {
+                        Some(p) => menu.insert(&private_item, p as i32 + 2),
+                        None => menu.append(&private_item),
+                    }
+
+                    let clean_action = gtk4::gio::SimpleAction::new("copy-clean-link", None);
+                    clean_action.connect_activate(clone!(#[strong] uri, move |_, _| {
+                        if let Some(display) = gtk4::gdk::Display::default() {
+                            display.clipboard().set_text(&clean_url(&uri));
+                        }
+                    }));
+                    let clean_item = webkit6::ContextMenuItem::from_gaction(
+                        &clean_action, "Copy Clean Link", None
+                    );
+                    match pos {
+                        Some(p) => menu.insert(&clean_item, p as i32 + 3),
+                        None => menu.append(&clean_item),
+                    }
+
+                    if !bw.private {
+                        let container_action = gtk4::gio::SimpleAction::new("open-link-in-container", Some(glib::VariantTy::STRING));
+                        container_action.connect_activate(clone!(
+                            #[strong] bw, #[strong] uri,
+                            move |_, name| {
+                                let name = name.and_then(|n| n.get::<String>());
+                                add_tab(&bw, None, Some(&uri), name.as_deref(), true);
+                            }
+                        ));
+                        let submenu = webkit6::ContextMenu::new();
+                        for (name, _) in CONTAINERS.with(|c| c.borrow().clone()) {
+                            submenu.append(&webkit6::ContextMenuItem::from_gaction(
+                                &container_action, &name, Some(&name.to_variant())
+                            ));
+                        }
+                        let container_item = webkit6::ContextMenuItem::with_submenu("Open Link in Container", &submenu);
+                        match pos {
+                            Some(p) => menu.insert(&container_item, p as i32 + 4),
+                            None => menu.append(&container_item),
+                        }
+                    }
                 }
             }
 
@@ -445,11 +1980,9 @@ fn add_tab(
                     // Open Image in New Tab
                     let open_action = gtk4::gio::SimpleAction::new("open-image-in-new-tab", None);
                     open_action.connect_activate(clone!(
-                        #[weak] notebook, #[weak] window, #[weak] url_bar,
-                        #[weak] back_button, #[weak] forward_button, #[weak] progress_bar, #[strong] app,
+                        #[strong] bw, #[strong] container,
                         move |_, _| {
-                            add_tab(&notebook, &window, &url_bar, &back_button,
-                                    &forward_button, &progress_bar, &app, None, Some(&uri));
+                            add_tab(&bw, None, Some(&uri), container.as_deref(), true);
                         }
                     ));
                     let open_item = webkit6::ContextMenuItem::from_gaction(
@@ -490,10 +2023,301 @@ fn add_tab(
 
             false
         }
-    ));
+    );
+
+    // Page fullscreen, such as video, covers the screen and hides the chrome until the page leaves it
+    webview.connect_enter_fullscreen(|webview| {
+        let Some(bw) = window_of(webview) else { return false };
+        bw.was_fullscreen.set(bw.window.is_fullscreen());
+        set_chrome_visible(&bw, false);
+        bw.window.fullscreen();
+        false
+    });
+    webview.connect_leave_fullscreen(|webview| {
+        let Some(bw) = window_of(webview) else { return false };
+        set_chrome_visible(&bw, true);
+        if !bw.was_fullscreen.get() { bw.window.unfullscreen(); }
+        false
+    });
+
+    // Connected before every other handler, so each one reads the clean URL through navigation_uri().
+    // Redirects are left alone, they are the server's to make.
+    webview.connect_decide_policy(|_, decision, decision_type| {
+        CLEAN_NAVIGATION.with(|c| c.replace(None));
+        if !matches!(decision_type, webkit6::PolicyDecisionType::NavigationAction | webkit6::PolicyDecisionType::NewWindowAction) {
+            return false;
+        }
+        if !STRIP_TRACKING.with(|s| *s.borrow()) { return false; }
+        let Some(nav) = decision.downcast_ref::<webkit6::NavigationPolicyDecision>() else { return false };
+        let Some(mut action) = nav.navigation_action() else { return false };
+        let Some(request) = action.request() else { return false };
+        let Some(uri) = request.uri().map(|u| u.to_string()) else { return false };
+        if action.is_redirect() || !uri.starts_with("http") || request.http_method().is_some_and(|m| m != "GET") { return false; }
+        let clean = clean_url(&uri);
+        if clean != uri { CLEAN_NAVIGATION.with(|c| c.replace(Some((uri, clean)))); }
+        false
+    });
+
+    // A kiosk stays in its one view: new windows load in place, rug pages and other apps are off limits
+    webview.connect_decide_policy(|webview, decision, decision_type| {
+        if !is_kiosk() { return false; }
+        if !matches!(decision_type, webkit6::PolicyDecisionType::NavigationAction | webkit6::PolicyDecisionType::NewWindowAction) {
+            return false;
+        }
+        let Some(nav) = decision.downcast_ref::<webkit6::NavigationPolicyDecision>() else { return false };
+        let Some(mut action) = nav.navigation_action() else { return false };
+        let Some(uri) = navigation_uri(&mut action) else { return false };
+        if uri.starts_with("rug:") || external_scheme(&uri).is_some() {
+            decision.ignore();
+        } else if decision_type == webkit6::PolicyDecisionType::NewWindowAction {
+            decision.ignore();
+            webview.load_uri(&uri);
+        } else {
+            return false;
+        }
+        true
+    });
+
+    webview.connect_decide_policy(|webview, decision, decision_type| {
+        if !matches!(decision_type, webkit6::PolicyDecisionType::NavigationAction | webkit6::PolicyDecisionType::NewWindowAction) {
+            return false;
+        }
+        let Some(nav) = decision.downcast_ref::<webkit6::NavigationPolicyDecision>() else { return false };
+        let Some(mut action) = nav.navigation_action() else { return false };
+        let Some(uri) = navigation_uri(&mut action) else { return false };
+        let Some(scheme) = external_scheme(&uri) else { return false };
+        let Some(bw) = window_of(webview) else { return false };
+        decision.ignore();
+        open_external(&bw, webview, &uri, &scheme, action.is_redirect());
+        true
+    });
+
+    // Middle or Ctrl+click opens a background tab, Ctrl+Shift+click a foreground one, Shift+click a window
+    webview.connect_decide_policy(|webview, decision, decision_type| {
+        if is_kiosk() { return false; }
+        if !matches!(decision_type, webkit6::PolicyDecisionType::NavigationAction | webkit6::PolicyDecisionType::NewWindowAction) {
+            return false;
+        }
+        let Some(nav) = decision.downcast_ref::<webkit6::NavigationPolicyDecision>() else { return false };
+        let Some(mut action) = nav.navigation_action() else { return false };
+        if action.navigation_type() != webkit6::NavigationType::LinkClicked { return false; }
+        let Some(uri) = navigation_uri(&mut action) else { return false };
+        let modifiers = gtk4::gdk::ModifierType::from_bits_truncate(action.modifiers());
+        let ctrl = modifiers.contains(gtk4::gdk::ModifierType::CONTROL_MASK);
+        let shift = modifiers.contains(gtk4::gdk::ModifierType::SHIFT_MASK);
+        let middle = action.mouse_button() == gtk4::gdk::BUTTON_MIDDLE;
+        let Some(bw) = window_of(webview) else { return false };
+        if middle || ctrl {
+            decision.ignore();
+            open_tab_beside(webview, &uri, shift);
+        } else if shift {
+            decision.ignore();
+            add_tab(&browser_window(&bw.app, bw.private), None, Some(&uri), container_of(webview).as_deref(), true);
+        } else {
+            return false;
+        }
+        true
+    });
+
+    // Sites assigned to a container always open in it, in a tab of their own
+    webview.connect_decide_policy(move |webview, decision, decision_type| {
+        if private || decision_type != webkit6::PolicyDecisionType::NavigationAction { return false; }
+        let Some(nav) = decision.downcast_ref::<webkit6::NavigationPolicyDecision>() else { return false };
+        let Some(mut action) = nav.navigation_action() else { return false };
+        let Some(uri) = navigation_uri(&mut action) else { return false };
+        let Some(target) = uri_host(&uri).and_then(|host| assigned_container(&host)) else { return false };
+        if container_of(webview).as_deref() == Some(target.as_str()) { return false; }
+        let Some(bw) = window_of(webview) else { return false };
+        decision.ignore();
+        let blank = !webview.can_go_back() && webview.uri().is_none_or(|u| u.is_empty() || u.starts_with("rug:"));
+        add_tab(&bw, None, Some(&uri), Some(&target), true);
+        if blank {
+            glib::idle_add_local_once(clone!(#[weak(rename_to = notebook)] bw.notebook, #[weak] webview, move || {
+                if let Some(n) = notebook.page_num(&webview) { notebook.remove_page(Some(n)); }
+            }));
+        }
+        true
+    });
+
+    // A pinned tab stays on its site; links elsewhere open beside it instead
+    webview.connect_decide_policy(|webview, decision, decision_type| {
+        if decision_type != webkit6::PolicyDecisionType::NavigationAction || !is_pinned(webview) { return false; }
+        let Some(nav) = decision.downcast_ref::<webkit6::NavigationPolicyDecision>() else { return false };
+        let Some(mut action) = nav.navigation_action() else { return false };
+        if action.navigation_type() != webkit6::NavigationType::LinkClicked { return false; }
+        let Some(uri) = navigation_uri(&mut action) else { return false };
+        let (Some(host), Some(current)) = (uri_host(&uri), webview.uri().and_then(|u| uri_host(&u))) else { return false };
+        if host_matches(&host, &current) || host_matches(&current, &host) { return false; }
+        let Some(bw) = window_of(webview) else { return false };
+        decision.ignore();
+        add_tab(&bw, None, Some(&uri), container_of(webview).as_deref(), true);
+        true
+    });
+
+    // http:// URI of a navigation we redirected to https://, until the upgrade commits
+    let https_pending: std::rc::Rc<RefCell<Option<String>>> = std::rc::Rc::new(RefCell::new(None));
+
+    webview.connect_decide_policy(clone!(#[strong] https_pending, move |webview, decision, decision_type| {
+        if decision_type != webkit6::PolicyDecisionType::NavigationAction { return false; }
+        let Some(nav) = decision.downcast_ref::<webkit6::NavigationPolicyDecision>() else { return false };
+        let Some(mut action) = nav.navigation_action() else { return false };
+        let Some(uri) = navigation_uri(&mut action) else { return false };
+        let Some(https) = https_upgrade(&uri, is_private(webview)) else { return false };
+        decision.ignore();
+        // An upgraded page redirecting straight back to http would loop forever
+        if action.is_redirect() && https_pending.borrow().as_deref() == Some(uri.as_str()) {
+            https_pending.replace(None);
+            webview.load_uri(&https_error_uri(&uri));
+        } else {
+            https_pending.replace(Some(uri.to_string()));
+            webview.load_uri(&https);
+        }
+        true
+    }));
+
+    // A per-site user agent only reaches the network if set before the request is sent,
+    // so a top-level GET navigation is re-issued once when it switches the agent
+    let resent_uri: std::rc::Rc<RefCell<Option<String>>> = std::rc::Rc::new(RefCell::new(None));
+    webview.connect_decide_policy(move |webview, decision, decision_type| {
+        if decision_type != webkit6::PolicyDecisionType::NavigationAction { return false; }
+        let Some(nav) = decision.downcast_ref::<webkit6::NavigationPolicyDecision>() else { return false };
+        let Some(mut action) = nav.navigation_action() else { return false };
+        let replay = matches!(
+            action.navigation_type(),
+            webkit6::NavigationType::BackForward | webkit6::NavigationType::Reload | webkit6::NavigationType::FormResubmitted
+        );
+        let Some(request) = action.request() else { return false };
+        let Some(uri) = navigation_uri(&mut action) else { return false };
+        if !uri.starts_with("http") { return false; }
+        let ua_changed = apply_user_agent(webview, &uri);
+        if replay || action.is_redirect() || request.http_method().is_some_and(|m| m != "GET") { return false; }
+        if resent_uri.take().as_deref() == Some(uri.as_str()) { return false; }
+        if !ua_changed { return false; }
+        decision.ignore();
+        request.set_uri(&uri);
+        resent_uri.replace(Some(uri));
+        webview.load_request(&request);
+        true
+    });
+
+    // Last: a navigation that stays in this view is loaded again without its tracking parameters.
+    // Only links followed in the top frame qualify, since a frame's navigation would replace the page.
+    let top_link: std::rc::Rc<RefCell<Option<String>>> = std::rc::Rc::new(RefCell::new(None));
+    if let Some(ucm) = webview.user_content_manager() {
+        ucm.register_script_message_handler("topLink", Some("rug"));
+        ucm.connect_script_message_received(Some("topLink"), clone!(#[strong] top_link, move |_, message| {
+            top_link.replace(Some(message.to_str().to_string()));
+        }));
+    }
+    webview.connect_decide_policy(move |webview, decision, decision_type| {
+        if decision_type != webkit6::PolicyDecisionType::NavigationAction { return false; }
+        let top_link = top_link.take();
+        let Some((tracked, clean)) = CLEAN_NAVIGATION.with(|c| c.take()) else { return false };
+        if top_link.as_deref() != Some(tracked.as_str()) { return false; }
+        let Some(nav) = decision.downcast_ref::<webkit6::NavigationPolicyDecision>() else { return false };
+        let Some(mut action) = nav.navigation_action() else { return false };
+        if action.navigation_type() != webkit6::NavigationType::LinkClicked { return false; }
+        let Some(request) = action.request() else { return false };
+        decision.ignore();
+        request.set_uri(&clean);
+        webview.load_request(&request);
+        true
+    });
+
+    webview.connect_load_changed(clone!(#[strong] https_pending, move |_, load_event| {
+        if load_event == webkit6::LoadEvent::Committed {
+            https_pending.replace(None);
+        }
+    }));
+
+    webview.connect_load_failed(clone!(#[strong] https_pending, move |webview, _, failing_uri, error| {
+        if error.matches(webkit6::NetworkError::Cancelled) { return false; }
+        let Some(http) = https_pending.borrow().clone() else { return false };
+        if https_upgrade(&http, is_private(webview)).as_deref() != Some(failing_uri) { return false; }
+        https_pending.replace(None);
+        webview.load_uri(&https_error_uri(&http));
+        true
+    }));
+
+    webview.connect_load_failed_with_tls_errors(clone!(#[strong] https_pending, move |webview, failing_uri, certificate, errors| {
+        let upgraded = https_pending.borrow().clone()
+            .filter(|http| https_upgrade(http, is_private(webview)).as_deref() == Some(failing_uri));
+        if let Some(http) = upgraded {
+            https_pending.replace(None);
+            webview.load_uri(&https_error_uri(&http));
+            return true;
+        }
+        let Some(host) = uri_host(failing_uri) else { return false };
+        TLS_ERRORS.with(|t| t.borrow_mut().insert(host, (certificate.clone(), errors)));
+        webview.load_uri(&format!("rug://tls-error?url={}", url_encode(failing_uri)));
+        true
+    }));
+
+    // The uri property moves as soon as a navigation starts, so a page could
+    // request a permission under the name of the site it is leaving for
+    let committed_origin = std::rc::Rc::new(RefCell::new(String::new()));
+    webview.connect_load_changed(clone!(#[strong] committed_origin, move |webview, load_event| {
+        if load_event == webkit6::LoadEvent::Committed {
+            let origin = webkit6::SecurityOrigin::for_uri(&webview.uri().unwrap_or_default()).to_str().to_string();
+            committed_origin.replace(origin);
+        }
+    }));
+
+    webview.connect_permission_request(move |webview, request| {
+        let Some((kind, what)) = permission_kind(request) else { return false };
+        let Some(bw) = window_of(webview) else { return false };
+        let origin = match request.downcast_ref::<webkit6::WebsiteDataAccessPermissionRequest>() {
+            Some(access) => access.current_domain().map(|d| d.to_string()).unwrap_or_default(),
+            None => committed_origin.borrow().clone(),
+        };
+        if origin.is_empty() { return false; }
+        match permission_decision(&origin, kind, bw.private) {
+            Some(true) => request.allow(),
+            Some(false) => request.deny(),
+            None => show_permission_prompt(&bw, webview, request, &origin, kind, what),
+        }
+        true
+    });
+
+    if let Some(ucm) = webview.user_content_manager() {
+        ucm.register_script_message_handler_with_reply("passwords", Some("rug"));
+        let unlock_offered = std::cell::Cell::new(false);
+        ucm.connect_script_message_with_reply_received(Some("passwords"), clone!(
+            #[weak] webview, #[upgrade_or] false,
+            move |_, message, reply| {
+                let Some(bw) = window_of(&webview) else { return false };
+                let ctx = message.context();
+                let message = message.to_json(0).and_then(|j| serde_json::from_str(&j).ok()).unwrap_or_default();
+                let logins = password_message(&bw, &webview, &message, &unlock_offered);
+                if let Some(ctx) = ctx {
+                    reply.return_value(&webkit6::javascriptcore::Value::new_string(&ctx, Some(&logins.to_string())));
+                }
+                true
+            }
+        ));
+    }
+
+    webview.connect_query_permission_state(|webview, query| {
+        let (Some(name), Some(origin)) = (query.name(), query.security_origin()) else { return false };
+        let kinds: &[&str] = match name.as_str() {
+            "geolocation" => &["geolocation"],
+            "notifications" => &["notifications"],
+            "camera" => &["camera", "camera-microphone"],
+            "microphone" => &["microphone", "camera-microphone"],
+            _ => return false,
+        };
+        let origin = origin.to_str();
+        let decision = kinds.iter().find_map(|k| permission_decision(&origin, k, is_private(webview)));
+        query.finish(match decision {
+            Some(true) => webkit6::PermissionState::Granted,
+            Some(false) => webkit6::PermissionState::Denied,
+            None => webkit6::PermissionState::Prompt,
+        });
+        true
+    });
 
     webview.connect_create(clone!(#[strong] app, move |webview, _| {
-        let new_webview = create_browser_window(&app, Some(webview));
+        let new_webview = create_browser_window(&app, Some(webview), private);
         new_webview.upcast::<gtk4::Widget>()
     }));
 
@@ -505,32 +2329,115 @@ fn add_tab(
     let favicon_img = Image::new();
     favicon_img.set_pixel_size(16);
     favicon_img.set_paintable(Some(&default_favicon()));
-    if PRIVATE.with(|p| *p.borrow()) {
+    if private {
         favicon_img.add_css_class("private-favicon");
     }
     let title_label = Label::new(Some("New Tab"));
     title_label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
     title_label.set_hexpand(true);
+    title_label.bind_property("label", &favicon_img, "tooltip-text").sync_create().build();
+    let audio_btn = Button::from_icon_name("audio-volume-high-symbolic");
+    audio_btn.set_has_frame(false);
+    audio_btn.set_visible(false);
+    audio_btn.set_action_name(Some("tabctx.toggle-mute"));
     let close_btn = Button::with_label("×");
     close_btn.set_has_frame(false);
     close_btn.set_margin_start(4);
+    close_btn.set_action_name(Some("tabctx.close"));
+    let container_color = container.as_ref().and_then(|c| container_color(c));
+    if let (Some(name), Some(color)) = (&container, &container_color) {
+        tab_box.append(&container_stripe(color));
+        tab_box.set_tooltip_text(Some(&format!("{} container", name)));
+    }
+    let group_chip = Label::new(None);
+    group_chip.set_visible(false);
+    tab_box.append(&group_chip);
     tab_box.append(&favicon_img);
     tab_box.append(&title_label);
+    tab_box.append(&audio_btn);
     tab_box.append(&close_btn);
 
+    // The same tab as a row of the vertical sidebar, mirroring the label above
+    let side_row = gtk4::ListBoxRow::new();
+    let side_box = GtkBox::new(Orientation::Horizontal, 6);
+    side_box.set_margin_start(4);
+    side_box.set_margin_end(4);
+    side_box.set_margin_top(2);
+    side_box.set_margin_bottom(2);
+    let side_favicon = Image::new();
+    side_favicon.set_pixel_size(16);
+    if private {
+        side_favicon.add_css_class("private-favicon");
+    }
+    favicon_img.bind_property("paintable", &side_favicon, "paintable").sync_create().build();
+    let side_title = Label::new(None);
+    side_title.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+    side_title.set_hexpand(true);
+    side_title.set_xalign(0.0);
+    title_label.bind_property("label", &side_title, "label").sync_create().build();
+    title_label.bind_property("label", &side_box, "tooltip-text").sync_create().build();
+    let side_audio = Button::new();
+    side_audio.set_has_frame(false);
+    side_audio.set_action_name(Some("tabctx.toggle-mute"));
+    for property in ["icon-name", "tooltip-text", "visible"] {
+        audio_btn.bind_property(property, &side_audio, property).sync_create().build();
+    }
+    let side_close = Button::with_label("×");
+    side_close.set_has_frame(false);
+    side_close.set_action_name(Some("tabctx.close"));
+    let side_details = GtkBox::new(Orientation::Horizontal, 6);
+    side_details.set_hexpand(true);
+    side_details.append(&side_title);
+    side_details.append(&side_audio);
+    side_details.append(&side_close);
+    if let Some(color) = &container_color {
+        side_box.append(&container_stripe(color));
+    }
+    let side_chip = Label::new(None);
+    for property in ["label", "use-markup", "visible"] {
+        group_chip.bind_property(property, &side_chip, property).sync_create().build();
+    }
+    tab_box.bind_property("css-classes", &side_box, "css-classes").sync_create().build();
+    side_box.append(&side_chip);
+    side_box.append(&side_favicon);
+    side_box.append(&side_details);
+    side_row.set_child(Some(&side_box));
+    webview.bind_property("visible", &side_row, "visible").sync_create().build();
+
+    let drag = gtk4::DragSource::new();
+    drag.set_actions(gtk4::gdk::DragAction::MOVE);
+    drag.connect_prepare(clone!(#[weak] webview, #[upgrade_or] None, move |_, _, _| {
+        Some(gtk4::gdk::ContentProvider::for_value(&webview.to_value()))
+    }));
+    side_box.add_controller(drag);
+    let drop = gtk4::DropTarget::new(WebView::static_type(), gtk4::gdk::DragAction::MOVE);
+    drop.connect_drop(clone!(#[weak] webview, #[weak] side_row, #[upgrade_or] false, move |_, value, _, _| {
+        let (Ok(dragged), Some(bw)) = (value.get::<WebView>(), window_of(&webview)) else { return false };
+        if bw.notebook.page_num(&dragged).is_some() {
+            bw.notebook.reorder_child(&dragged, Some(side_row.index() as u32));
+        } else {
+            move_tab(&dragged, &bw, Some(side_row.index() as u32));
+        }
+        true
+    }));
+    side_box.add_controller(drop);
+
     // Right-click context menu on tab label
     let tab_menu_model = gtk4::gio::Menu::new();
-    tab_menu_model.append(Some("Open in New Window"), Some("tabctx.open-new-window"));
+    let window_section = gtk4::gio::Menu::new();
+    tab_menu_model.append_section(None, &window_section);
+    let pin_section = gtk4::gio::Menu::new();
+    pin_section.append(Some("Pin Tab"), Some("tabctx.pin"));
+    tab_menu_model.append_section(None, &pin_section);
+    let group_section = gtk4::gio::Menu::new();
+    tab_menu_model.append_section(None, &group_section);
     let mute_section = gtk4::gio::Menu::new();
-    mute_section.append(Some("Mute Tab"), Some("tabctx.toggle-mute"));
     tab_menu_model.append_section(None, &mute_section);
     let close_section = gtk4::gio::Menu::new();
+    close_section.append(Some("Duplicate Tab"), Some("tabctx.duplicate"));
     close_section.append(Some("Close Tab"), Some("tabctx.close"));
     close_section.append(Some("Close Other Tabs"), Some("tabctx.close-others"));
     tab_menu_model.append_section(None, &close_section);
-    let tab_popup = gtk4::PopoverMenu::from_model(Some(&tab_menu_model));
-    tab_popup.set_parent(&tab_box);
-    tab_popup.set_has_arrow(false);
 
     let tab_action_group = gtk4::gio::SimpleActionGroup::new();
 
@@ -538,38 +2445,217 @@ fn add_tab(
     open_new_window_action.connect_activate(clone!(
         #[strong] app, #[weak] webview,
         move |_, _| {
-            let uri = webview.uri().unwrap_or_default().to_string();
-            let new_wv = create_browser_window(&app, None);
-            if !uri.is_empty() && !uri.starts_with("rug:") {
-                new_wv.load_uri(&uri);
-            }
+            move_tab(&webview, &browser_window(&app, private), None);
         }
     ));
     tab_action_group.add_action(&open_new_window_action);
 
-    let close_tab_action = gtk4::gio::SimpleAction::new("close", None);
-    close_tab_action.connect_activate(clone!(
-        #[weak] notebook, #[weak] webview, #[weak] window,
-        move |_, _| {
-            if let Some(n) = notebook.page_num(&webview) {
-                notebook.remove_page(Some(n));
-            }
-            if notebook.n_pages() == 0 {
-                window.close();
-            }
+    let move_to_window_action = gtk4::gio::SimpleAction::new("move-to-window", Some(glib::VariantTy::STRING));
+    move_to_window_action.connect_activate(clone!(#[weak] webview, move |_, id| {
+        let Some(id) = id.and_then(|id| id.get::<String>()) else { return };
+        if let Some(target) = WINDOWS.with(|w| w.borrow().iter().find(|bw| bw.id == id).cloned()) {
+            move_tab(&webview, &target, None);
         }
-    ));
-    tab_action_group.add_action(&close_tab_action);
+    }));
+    tab_action_group.add_action(&move_to_window_action);
 
-    let close_others_action = gtk4::gio::SimpleAction::new("close-others", None);
-    close_others_action.connect_activate(clone!(
-        #[weak] notebook, #[weak] webview,
+    // Pinned tabs show only their favicon, in the strip and in the sidebar alike;
+    // the first tab of a group carries its chip, which is all that's left when collapsed
+    let refresh_group_action = gtk4::gio::SimpleAction::new("refresh-group", None);
+    refresh_group_action.connect_activate(clone!(
+        #[weak] webview, #[weak] tab_box, #[weak] group_chip, #[weak] favicon_img,
+        #[weak] title_label, #[weak] close_btn, #[weak] side_favicon, #[weak] side_title, #[weak] side_close,
         move |_, _| {
-            let count = notebook.n_pages();
-            let my_idx = notebook.page_num(&webview);
-            for i in (0..count).rev() {
-                if Some(i) != my_idx {
-                    notebook.remove_page(Some(i));
+            let Some(notebook) = notebook_of(&webview) else { return };
+            let group = group_of(&webview)
+                .and_then(|id| with_group(id, |g| (id, g.name.clone(), g.color.clone(), g.collapsed)));
+            let first = group.as_ref().is_some_and(|(id, ..)| group_members(&notebook, *id).first() == Some(&webview));
+            let collapsed = first && group.as_ref().is_some_and(|(.., collapsed)| *collapsed);
+            let compact = is_pinned(&webview) || collapsed;
+            match &group {
+                Some((id, name, color, _)) if first => {
+                    let count = if collapsed { format!(" {}", group_members(&notebook, *id).len()) } else { String::new() };
+                    group_chip.set_markup(&format!(
+                        "<span background='{}' foreground='white'> {}{} </span>", color, pango_esc(name), count
+                    ));
+                }
+                Some((_, _, color, _)) => group_chip.set_markup(&format!("<span foreground='{}'>●</span>", color)),
+                None => {}
+            }
+            group_chip.set_visible(group.is_some());
+            tab_box.set_size_request(if compact { -1 } else { 160 }, -1);
+            for widget in [title_label.upcast_ref::<gtk4::Widget>(), close_btn.upcast_ref(), side_title.upcast_ref(), side_close.upcast_ref()] {
+                widget.set_visible(!compact);
+            }
+            favicon_img.set_visible(!collapsed);
+            side_favicon.set_visible(!collapsed);
+        }
+    ));
+    tab_action_group.add_action(&refresh_group_action);
+
+    let pin_action = gtk4::gio::SimpleAction::new("pin", None);
+    pin_action.set_enabled(!private);
+    pin_action.connect_activate(clone!(
+        #[weak] webview,
+        move |_, _| {
+            let Some(notebook) = notebook_of(&webview) else { return };
+            set_pinned(&notebook, &webview, !is_pinned(&webview));
+            refresh_groups(&notebook);
+        }
+    ));
+    tab_action_group.add_action(&pin_action);
+
+    // Ctrl+click selects several tabs for "Add to New Group"
+    let new_group_action = gtk4::gio::SimpleAction::new("new-group", None);
+    new_group_action.set_enabled(!private);
+    new_group_action.connect_activate(clone!(
+        #[weak] webview, #[weak] tab_action_group,
+        move |_, _| {
+            let Some(bw) = window_of(&webview) else { return };
+            let mut tabs = vec![webview.clone()];
+            for wv in notebook_tabs(&bw.notebook) {
+                let Some(label) = bw.notebook.tab_label(&wv) else { continue };
+                if label.has_css_class("tab-selected") {
+                    label.remove_css_class("tab-selected");
+                    if wv != webview { tabs.push(wv); }
+                }
+            }
+            let id = create_group(&bw, "", None);
+            add_to_group(&bw.notebook, id, &tabs);
+            tab_action_group.activate_action("rename-group", None);
+        }
+    ));
+    tab_action_group.add_action(&new_group_action);
+
+    let add_to_group_action = gtk4::gio::SimpleAction::new("add-to-group", Some(glib::VariantTy::UINT32));
+    add_to_group_action.connect_activate(clone!(
+        #[weak] webview,
+        move |_, id| {
+            let Some(notebook) = notebook_of(&webview) else { return };
+            if let Some(id) = id.and_then(|id| id.get::<u32>()) {
+                add_to_group(&notebook, id, std::slice::from_ref(&webview));
+            }
+        }
+    ));
+    tab_action_group.add_action(&add_to_group_action);
+
+    let ungroup_action = gtk4::gio::SimpleAction::new("ungroup", None);
+    ungroup_action.connect_activate(clone!(#[weak] webview, move |_, _| {
+        let Some(notebook) = notebook_of(&webview) else { return };
+        remove_from_group(&notebook, &webview);
+    }));
+    tab_action_group.add_action(&ungroup_action);
+
+    let rename_group_action = gtk4::gio::SimpleAction::new("rename-group", None);
+    rename_group_action.connect_activate(clone!(
+        #[weak] webview, #[weak] tab_box, #[weak] side_box,
+        move |_, _| {
+            let Some(notebook) = notebook_of(&webview) else { return };
+            let Some(id) = group_of(&webview) else { return };
+            let entry = Entry::new();
+            entry.set_text(&with_group(id, |g| g.name.clone()).unwrap_or_default());
+            entry.set_placeholder_text(Some("Group name"));
+            let popover = gtk4::Popover::new();
+            popover.set_child(Some(&entry));
+            popover.set_parent(if tab_box.is_mapped() { &tab_box } else { &side_box });
+            popover.connect_closed(|popover| {
+                let popover = popover.clone();
+                glib::idle_add_local_once(move || popover.unparent());
+            });
+            entry.connect_activate(clone!(#[weak] popover, #[weak] notebook, move |entry| {
+                // An empty name keeps the current one rather than leaving the group unlabeled
+                let name = entry.text().trim().to_string();
+                if !name.is_empty() {
+                    with_group(id, |g| g.name = name);
+                    refresh_groups(&notebook);
+                    save_settings();
+                }
+                popover.popdown();
+            }));
+            popover.popup();
+            entry.grab_focus();
+        }
+    ));
+    tab_action_group.add_action(&rename_group_action);
+
+    let group_color_action = gtk4::gio::SimpleAction::new("group-color", Some(glib::VariantTy::STRING));
+    group_color_action.connect_activate(clone!(
+        #[weak] webview,
+        move |_, color| {
+            let Some(notebook) = notebook_of(&webview) else { return };
+            let (Some(id), Some(color)) = (group_of(&webview), color.and_then(|c| c.get::<String>())) else { return };
+            with_group(id, |g| g.color = color);
+            refresh_groups(&notebook);
+            save_settings();
+        }
+    ));
+    tab_action_group.add_action(&group_color_action);
+
+    let toggle_group_action = gtk4::gio::SimpleAction::new("toggle-group", None);
+    toggle_group_action.connect_activate(clone!(#[weak] webview, move |_, _| {
+        let Some(notebook) = notebook_of(&webview) else { return };
+        let Some(id) = group_of(&webview) else { return };
+        let collapsed = with_group(id, |g| g.collapsed).unwrap_or(false);
+        set_group_collapsed(&notebook, id, !collapsed);
+    }));
+    tab_action_group.add_action(&toggle_group_action);
+
+    let save_group_action = gtk4::gio::SimpleAction::new("save-group", None);
+    save_group_action.connect_activate(clone!(#[weak] webview, move |_, _| {
+        if let Some(id) = group_of(&webview) { save_group(id); }
+    }));
+    tab_action_group.add_action(&save_group_action);
+
+    let close_group_action = gtk4::gio::SimpleAction::new("close-group", None);
+    close_group_action.connect_activate(clone!(#[weak] webview, move |_, _| {
+        let Some(bw) = window_of(&webview) else { return };
+        if let Some(id) = group_of(&webview) { close_group(&bw, id); }
+    }));
+    tab_action_group.add_action(&close_group_action);
+
+    let close_tab_action = gtk4::gio::SimpleAction::new("close", None);
+    close_tab_action.connect_activate(clone!(#[weak] webview, move |_, _| close_tab(&webview)));
+    tab_action_group.add_action(&close_tab_action);
+
+    // Emitted once the page agrees to close, after any beforeunload prompt, or by window.close()
+    webview.connect_close(|webview| {
+        let Some(BrowserWindow { notebook, closing, .. }) = window_of(webview) else { return };
+        // Closing the whole window keeps pinned tabs and groups for the next start
+        if !closing.get() && (is_pinned(webview) || group_of(webview).is_some()) {
+            PINNED_TABS.with(|p| p.borrow_mut().retain(|t| !t.is(webview)));
+            TAB_GROUPS.with(|g| {
+                let mut groups = g.borrow_mut();
+                groups.iter_mut().for_each(|g| g.tabs.retain(|t| !t.is(webview)));
+                groups.retain(|g| !g.tabs.is_empty());
+            });
+            save_settings();
+        }
+        if let Some(n) = notebook.page_num(webview) {
+            notebook.remove_page(Some(n));
+        }
+        // Cookies a blocked site set while open go with its tab
+        if let Some(ns) = webview.network_session() {
+            purge_blocked_cookies(&ns);
+        }
+        // Closing a window asks one tab at a time, so a tab that refuses stops the rest
+        if closing.get() {
+            glib::idle_add_local_once(move || {
+                if let Some(next) = notebook_tabs(&notebook).into_iter().next() { next.try_close(); }
+            });
+        }
+    });
+
+    let close_others_action = gtk4::gio::SimpleAction::new("close-others", None);
+    close_others_action.connect_activate(clone!(
+        #[weak] webview,
+        move |_, _| {
+            let Some(notebook) = notebook_of(&webview) else { return };
+            let count = notebook.n_pages();
+            let my_idx = notebook.page_num(&webview);
+            for i in (0..count).rev() {
+                let Some(other) = notebook.nth_page(Some(i)).and_then(|p| p.downcast::<WebView>().ok()) else { continue };
+                if Some(i) != my_idx && !is_pinned(&other) {
+                    close_tab(&other);
                 }
             }
         }
@@ -585,57 +2671,147 @@ fn add_tab(
     ));
     tab_action_group.add_action(&toggle_mute_action);
 
-    tab_box.insert_action_group("tabctx", Some(&tab_action_group));
+    let mute_others_action = gtk4::gio::SimpleAction::new("mute-others", None);
+    mute_others_action.connect_activate(clone!(#[weak] webview, move |_, _| {
+        let Some(notebook) = notebook_of(&webview) else { return };
+        for other in notebook_tabs(&notebook).iter().filter(|wv| **wv != webview) {
+            other.set_is_muted(true);
+        }
+    }));
+    tab_action_group.add_action(&mute_others_action);
+
+    let mute_site_action = gtk4::gio::SimpleAction::new("mute-site", None);
+    mute_site_action.connect_activate(clone!(#[weak] webview, move |_, _| {
+        let Some(host) = webview.uri().and_then(|u| uri_host(&u)) else { return };
+        let private = is_private(&webview);
+        set_site_muted(&host, !is_site_muted(&host, private), private);
+    }));
+    tab_action_group.add_action(&mute_site_action);
+
+    let duplicate_action = gtk4::gio::SimpleAction::new("duplicate", None);
+    duplicate_action.connect_activate(clone!(#[weak] webview, move |_, _| { duplicate_tab(&webview, 0); }));
+    tab_action_group.add_action(&duplicate_action);
+
+    let discard_action = gtk4::gio::SimpleAction::new("discard", None);
+    discard_action.connect_activate(clone!(#[weak] webview, move |_, _| discard_tab(&webview)));
+    tab_action_group.add_action(&discard_action);
+
+    // Both the notebook label and the sidebar row open the same right-click menu
+    for target in [&tab_box, &side_box] {
+        target.insert_action_group("tabctx", Some(&tab_action_group));
+        let tab_popup = gtk4::PopoverMenu::from_model(Some(&tab_menu_model));
+        tab_popup.set_parent(target);
+        tab_popup.set_has_arrow(false);
+
+        let tab_right_click = gtk4::GestureClick::new();
+        tab_right_click.set_button(3);
+        tab_right_click.connect_pressed(clone!(
+            #[weak] tab_popup, #[weak] window_section, #[weak] pin_section, #[weak] group_section, #[weak] mute_section,
+            #[weak] webview,
+            move |gesture, _, x, y| {
+                let Some(bw) = window_of(&webview) else { return };
+                fill_window_menu(&window_section, &bw);
+                pin_section.remove_all();
+                pin_section.append(Some(if is_pinned(&webview) { "Unpin Tab" } else { "Pin Tab" }), Some("tabctx.pin"));
+                fill_group_menu(&group_section, &bw, &webview);
+                while mute_section.n_items() > 0 { mute_section.remove(0); }
+                let label = if webview.is_muted() { "Unmute Tab" } else { "Mute Tab" };
+                mute_section.append(Some(label), Some("tabctx.toggle-mute"));
+                mute_section.append(Some("Mute Other Tabs"), Some("tabctx.mute-others"));
+                if let Some(host) = webview.uri().and_then(|u| uri_host(&u)) {
+                    let label = if is_site_muted(&host, is_private(&webview)) { "Unmute Site" } else { "Mute Site" };
+                    mute_section.append(Some(label), Some("tabctx.mute-site"));
+                }
+                if can_discard(&webview) {
+                    mute_section.append(Some("Discard Tab"), Some("tabctx.discard"));
+                }
+                gesture.set_state(gtk4::EventSequenceState::Claimed);
+                let rect = gtk4::gdk::Rectangle::new(x as i32, y as i32, 1, 1);
+                tab_popup.set_pointing_to(Some(&rect));
+                tab_popup.popup();
+            }
+        ));
+        target.add_controller(tab_right_click);
 
-    let tab_right_click = gtk4::GestureClick::new();
-    tab_right_click.set_button(3);
-    tab_right_click.connect_pressed(clone!(
-        #[weak] tab_popup, #[weak] mute_section, #[weak] webview,
-        move |gesture, _, x, y| {
-            while mute_section.n_items() > 0 { mute_section.remove(0); }
-            let label = if webview.is_muted() { "Unmute Tab" } else { "Mute Tab" };
-            mute_section.append(Some(label), Some("tabctx.toggle-mute"));
+        let middle_click = gtk4::GestureClick::new();
+        middle_click.set_button(gtk4::gdk::BUTTON_MIDDLE);
+        middle_click.connect_released(clone!(#[weak] webview, move |gesture, _, _, _| {
             gesture.set_state(gtk4::EventSequenceState::Claimed);
-            let rect = gtk4::gdk::Rectangle::new(x as i32, y as i32, 1, 1);
-            tab_popup.set_pointing_to(Some(&rect));
-            tab_popup.popup();
-        }
-    ));
-    tab_box.add_controller(tab_right_click);
+            close_tab(&webview);
+        }));
+        target.add_controller(middle_click);
+
+        let select_click = gtk4::GestureClick::new();
+        select_click.connect_pressed(clone!(#[weak] tab_box, move |gesture, _, _, _| {
+            if !gesture.current_event_state().contains(gtk4::gdk::ModifierType::CONTROL_MASK) { return; }
+            gesture.set_state(gtk4::EventSequenceState::Claimed);
+            if tab_box.has_css_class("tab-selected") {
+                tab_box.remove_css_class("tab-selected");
+            } else {
+                tab_box.add_css_class("tab-selected");
+            }
+        }));
+        target.add_controller(select_click);
+    }
 
-    let is_private = PRIVATE.with(|p| *p.borrow());
+    for chip in [&group_chip, &side_chip] {
+        let chip_click = gtk4::GestureClick::new();
+        chip_click.connect_pressed(clone!(#[weak] tab_action_group, move |gesture, _, _, _| {
+            gesture.set_state(gtk4::EventSequenceState::Claimed);
+            tab_action_group.activate_action("toggle-group", None);
+        }));
+        chip.add_controller(chip_click);
+    }
 
     webview.connect_notify_local(
         Some("title"),
         clone!(#[weak] title_label, #[weak] webview, move |_, _| {
+            // A discarded tab keeps showing what it had loaded
+            if is_discarded(&webview) { return; }
             let title = webview.title().unwrap_or_default();
             let base = if title.is_empty() { "New Tab".to_string() } else { title.to_string() };
-            let base = if webview.is_muted() { format!("{} (muted)", base) } else { base };
-            let display = if is_private { format!("(Private) {}", base) } else { base };
+            let display = if private { format!("(Private) {}", base) } else { base };
             title_label.set_text(&display);
-            let uri = webview.uri().unwrap_or_default();
-            update_recent(&uri, &title);
+            if !private {
+                let uri = webview.uri().unwrap_or_default();
+                update_recent(&uri, &title);
+            }
         }),
     );
 
-    webview.connect_notify_local(
-        Some("is-muted"),
-        clone!(#[weak] title_label, #[weak] webview, move |_, _| {
-            let title = webview.title().unwrap_or_default();
-            let base = if title.is_empty() { "New Tab".to_string() } else { title.to_string() };
-            let base = if webview.is_muted() { format!("{} (muted)", base) } else { base };
-            let display = if is_private { format!("(Private) {}", base) } else { base };
-            title_label.set_text(&display);
-        }),
-    );
+    // The speaker shows while the tab makes sound, and stays while it is muted
+    for property in ["is-muted", "is-playing-audio"] {
+        webview.connect_notify_local(Some(property), clone!(#[weak] audio_btn, move |webview, _| {
+            let muted = webview.is_muted();
+            audio_btn.set_icon_name(if muted { "audio-volume-muted-symbolic" } else { "audio-volume-high-symbolic" });
+            audio_btn.set_tooltip_text(Some(if muted { "Unmute Tab" } else { "Mute Tab" }));
+            audio_btn.set_visible(muted || webview.is_playing_audio());
+            if let Some(notebook) = notebook_of(webview) { refresh_audio_indicator(&notebook); }
+        }));
+    }
+
+    // Muted sites stay muted wherever they load; leaving one lifts the mute it caused
+    let site_muted = std::rc::Rc::new(std::cell::Cell::new(false));
+    webview.connect_uri_notify(move |webview| {
+        let muted = webview.uri().and_then(|u| uri_host(&u)).is_some_and(|h| is_site_muted(&h, is_private(webview)));
+        if muted {
+            webview.set_is_muted(true);
+        } else if site_muted.get() {
+            webview.set_is_muted(false);
+        }
+        site_muted.set(muted);
+    });
 
     webview.connect_load_changed(clone!(
         #[weak] favicon_img, #[weak] webview,
         move |_, load_event| {
+            if load_event == webkit6::LoadEvent::Finished {
+                remember_tab_uri(&webview);
+            }
             if load_event == webkit6::LoadEvent::Finished {
                 if let Some(texture) = webview.favicon() {
                     favicon_img.set_paintable(Some(&texture));
-                    if let Some(uri) = webview.uri() {
+                    if let Some(uri) = webview.uri().filter(|_| !private) {
                         update_recent_favicon(&uri, &texture);
                     }
                 }
@@ -644,10 +2820,11 @@ fn add_tab(
     ));
 
     webview.connect_favicon_notify(clone!(#[weak] favicon_img, move |webview| {
+        if is_discarded(webview) { return; }
         let favicon = webview.favicon();
         if let Some(texture) = &favicon {
             favicon_img.set_paintable(Some(texture));
-            if let Some(uri) = webview.uri() {
+            if let Some(uri) = webview.uri().filter(|_| !private) {
                 update_recent_favicon(&uri, texture);
             }
         } else {
@@ -655,26 +2832,845 @@ fn add_tab(
         }
     }));
 
+    // Wires the tab into the window it has just been added to, whether it was opened there
+    // or dragged or moved over from another window
+    let page_handlers = std::rc::Rc::new(RefCell::new(Vec::new()));
+    let collapse_binding: std::rc::Rc<RefCell<Option<glib::Binding>>> = std::rc::Rc::new(RefCell::new(None));
+    let attach_action = gtk4::gio::SimpleAction::new("attach", None);
+    attach_action.connect_activate(clone!(
+        #[weak] webview, #[weak] side_row, #[weak] side_details, #[strong] page_handlers, #[strong] collapse_binding,
+        move |_, _| {
+            let Some(bw) = window_of(&webview) else { return };
+            let Some(index) = bw.notebook.page_num(&webview) else { return };
+            bw.tab_list.insert(&side_row, index as i32);
+            if bw.notebook.current_page() == Some(index) { bw.tab_list.select_row(Some(&side_row)); }
+            collapse_binding.replace(Some(
+                bw.sidebar_collapse.bind_property("active", &side_details, "visible").invert_boolean().sync_create().build()
+            ));
+            bw.notebook.set_tab_reorderable(&webview, true);
+            bw.notebook.set_tab_detachable(&webview, true);
+            bw.notebook.page(&webview).set_tab_expand(false);
+            bw.notebook.page(&webview).set_tab_fill(true);
+            // Groups belong to a single window, so a tab moved elsewhere leaves its group
+            if group_of(&webview).and_then(|id| with_group(id, |g| g.window != bw.id)).unwrap_or(false) {
+                TAB_GROUPS.with(|g| {
+                    let mut groups = g.borrow_mut();
+                    groups.iter_mut().for_each(|g| g.tabs.retain(|t| !t.is(&webview)));
+                    groups.retain(|g| !g.tabs.is_empty());
+                });
+                save_settings();
+            }
+
+            page_handlers.borrow_mut().push(bw.notebook.connect_page_reordered(clone!(
+                #[weak] webview, #[weak] side_row, #[weak(rename_to = tab_list)] bw.tab_list,
+                move |_, child, index| {
+                    if child != webview.upcast_ref::<gtk4::Widget>() { return; }
+                    tab_list.remove(&side_row);
+                    tab_list.insert(&side_row, index as i32);
+                    tab_list.select_row(Some(&side_row));
+                }
+            )));
+            page_handlers.borrow_mut().push(bw.notebook.connect_page_removed(clone!(
+                #[weak] webview, #[weak] side_row, #[weak(rename_to = tab_list)] bw.tab_list,
+                #[strong] page_handlers, #[strong] collapse_binding,
+                move |notebook, child, _| {
+                    if child != webview.upcast_ref::<gtk4::Widget>() { return; }
+                    tab_list.remove(&side_row);
+                    if let Some(binding) = collapse_binding.take() { binding.unbind(); }
+                    for id in page_handlers.take() { notebook.disconnect(id); }
+                }
+            )));
+            if is_pinned(&webview) {
+                bw.notebook.reorder_child(&webview, Some(pinned_count(&bw.notebook) - 1));
+            }
+            refresh_groups(&bw.notebook);
+            refresh_audio_indicator(&bw.notebook);
+        }
+    ));
+    tab_action_group.add_action(&attach_action);
+
     let page_idx = notebook.append_page(&webview, Some(&tab_box));
-    notebook.set_tab_reorderable(&webview, true);
-    notebook.set_current_page(Some(page_idx));
-    notebook.page(&webview).set_tab_expand(false);
-    notebook.page(&webview).set_tab_fill(true);
+    if select { notebook.set_current_page(Some(page_idx)); }
 
-    close_btn.connect_clicked(clone!(#[weak] notebook, #[weak] webview, #[weak] window, move |_| {
-        if let Some(n) = notebook.page_num(&webview) {
-            notebook.remove_page(Some(n));
+    webview
+}
+
+// Private windows keep their own choices in memory, falling back to the saved list
+fn is_site_muted(host: &str, private: bool) -> bool {
+    let session = if private {
+        PRIVATE_MUTED_SITES.with(|m| m.borrow().iter().find(|(h, _)| h == host).map(|(_, muted)| *muted))
+    } else {
+        None
+    };
+    session.unwrap_or_else(|| MUTED_SITES.with(|m| m.borrow().iter().any(|h| h == host)))
+}
+
+fn set_site_muted(host: &str, muted: bool, private: bool) {
+    if private {
+        PRIVATE_MUTED_SITES.with(|m| {
+            let mut sites = m.borrow_mut();
+            sites.retain(|(h, _)| h != host);
+            sites.push((host.to_string(), muted));
+        });
+    } else {
+        MUTED_SITES.with(|m| {
+            let mut sites = m.borrow_mut();
+            sites.retain(|h| h != host);
+            if muted { sites.push(host.to_string()); }
+        });
+        save_settings();
+    }
+    for webview in all_webviews() {
+        if webview.uri().and_then(|u| uri_host(&u)).as_deref() == Some(host) {
+            webview.set_is_muted(is_site_muted(host, is_private(&webview)));
         }
-        if notebook.n_pages() == 0 {
-            window.close();
+    }
+}
+
+fn refresh_audio_indicator(notebook: &Notebook) {
+    let Some(bw) = WINDOWS.with(|w| w.borrow().iter().find(|bw| &bw.notebook == notebook).cloned()) else { return };
+    let playing = notebook_tabs(notebook).iter().filter(|wv| wv.is_playing_audio()).count();
+    bw.audio_button.set_visible(playing > 0);
+    bw.audio_button.set_tooltip_text(Some(&match playing {
+        1 => "1 tab playing sound".to_string(),
+        n => format!("{} tabs playing sound", n),
+    }));
+}
+
+// Toolbar list of this window's tabs that are making sound
+fn audio_menu_button(notebook: &Notebook) -> gtk4::MenuButton {
+    let list = ListBox::new();
+    list.set_selection_mode(gtk4::SelectionMode::None);
+    let popover = gtk4::Popover::new();
+    popover.set_child(Some(&list));
+    // Rows keep the tab they were built for, so tabs that start or stop playing meanwhile don't shift them
+    let rows: std::rc::Rc<RefCell<Vec<glib::WeakRef<WebView>>>> = std::rc::Rc::new(RefCell::new(Vec::new()));
+    popover.connect_show(clone!(#[weak] notebook, #[weak] list, #[strong] rows, move |_| {
+        while let Some(child) = list.first_child() { list.remove(&child); }
+        rows.borrow_mut().clear();
+        for webview in notebook_tabs(&notebook).into_iter().filter(|wv| wv.is_playing_audio()) {
+            rows.borrow_mut().push(webview.downgrade());
+            let row = tab_row(&webview, None);
+            let mute = Button::from_icon_name(if webview.is_muted() { "audio-volume-muted-symbolic" } else { "audio-volume-high-symbolic" });
+            mute.set_has_frame(false);
+            mute.set_tooltip_text(Some(if webview.is_muted() { "Unmute Tab" } else { "Mute Tab" }));
+            mute.connect_clicked(clone!(#[weak] webview, move |mute| {
+                webview.set_is_muted(!webview.is_muted());
+                mute.set_icon_name(if webview.is_muted() { "audio-volume-muted-symbolic" } else { "audio-volume-high-symbolic" });
+            }));
+            if let Some(row_box) = row.child().and_downcast::<GtkBox>() { row_box.append(&mute); }
+            list.append(&row);
         }
     }));
+    list.connect_row_activated(clone!(#[weak] popover, #[strong] rows, move |_, row| {
+        let Some(webview) = rows.borrow().get(row.index() as usize).and_then(|w| w.upgrade()) else { return };
+        popover.popdown();
+        switch_to_tab(&webview);
+    }));
+    let button = gtk4::MenuButton::new();
+    button.set_icon_name("audio-volume-high-symbolic");
+    button.set_visible(false);
+    button.set_popover(Some(&popover));
+    button
+}
 
-    webview
+fn touch_mru(webview: &WebView) {
+    TAB_MRU.with(|m| {
+        let mut mru = m.borrow_mut();
+        mru.retain(|(w, _)| w.upgrade().is_some_and(|w| &w != webview));
+        let view = glib::WeakRef::new();
+        view.set(Some(webview));
+        mru.insert(0, (view, std::time::Instant::now()));
+    });
 }
 
-fn create_browser_window(app: &Application, related_view: Option<&WebView>) -> WebView {
-    let title = if PRIVATE.with(|i| *i.borrow()) { "rug — private browsing" } else { "rug" };
+// Tabs that were never selected count as used from when they were first seen here
+fn last_used(webview: &WebView) -> std::time::Instant {
+    TAB_MRU.with(|m| {
+        let mut mru = m.borrow_mut();
+        if let Some((_, at)) = mru.iter().find(|(w, _)| w.upgrade().as_ref() == Some(webview)) {
+            return *at;
+        }
+        let view = glib::WeakRef::new();
+        view.set(Some(webview));
+        let now = std::time::Instant::now();
+        mru.push((view, now));
+        now
+    })
+}
+
+fn is_discarded(webview: &WebView) -> bool {
+    DISCARDED_TABS.with(|d| d.borrow().iter().any(|w| w.upgrade().as_ref() == Some(webview)))
+}
+
+fn note_shared_process(opener: &WebView, webview: &WebView) {
+    SHARED_PROCESSES.with(|p| {
+        let mut processes = p.borrow_mut();
+        processes.retain(|views| views.iter().filter(|w| w.upgrade().is_some()).count() > 1);
+        let view = glib::WeakRef::new();
+        view.set(Some(webview));
+        match processes.iter_mut().find(|views| views.iter().any(|w| w.upgrade().as_ref() == Some(opener))) {
+            Some(views) => views.push(view),
+            None => {
+                let first = glib::WeakRef::new();
+                first.set(Some(opener));
+                processes.push(vec![first, view]);
+            }
+        }
+    });
+}
+
+fn shares_web_process(webview: &WebView) -> bool {
+    SHARED_PROCESSES.with(|p| p.borrow().iter().any(|views| {
+        views.iter().any(|w| w.upgrade().as_ref() == Some(webview))
+            && views.iter().filter_map(|w| w.upgrade()).any(|w| &w != webview)
+    }))
+}
+
+// Terminating a process shared with another live view would crash that view too
+fn can_discard(webview: &WebView) -> bool {
+    !is_discarded(webview)
+        && !shares_web_process(webview)
+        && !is_pinned(webview)
+        && !webview.is_playing_audio()
+        && webview.uri().is_some_and(|u| !u.is_empty())
+        && notebook_of(webview).is_some_and(|notebook| !is_active_tab(&notebook, webview))
+}
+
+// Unloads the page but keeps the view, so its title, favicon and back/forward list survive
+fn discard_tab(webview: &WebView) {
+    if !can_discard(webview) { return; }
+    let view = glib::WeakRef::new();
+    view.set(Some(webview));
+    DISCARDED_TABS.with(|d| d.borrow_mut().push(view));
+    webview.terminate_web_process();
+    if let Some(label) = notebook_of(webview).and_then(|notebook| notebook.tab_label(webview)) {
+        label.add_css_class("discarded");
+    }
+}
+
+fn restore_discarded(webview: &WebView) {
+    if !is_discarded(webview) { return; }
+    DISCARDED_TABS.with(|d| d.borrow_mut().retain(|w| w.upgrade().is_some_and(|w| &w != webview)));
+    if let Some(label) = notebook_of(webview).and_then(|notebook| notebook.tab_label(webview)) {
+        label.remove_css_class("discarded");
+    }
+    webview.reload();
+}
+
+// Under memory pressure every eligible background tab goes, not just the idle ones
+fn discard_idle_tabs(low_memory: bool) {
+    let minutes = DISCARD_AFTER_MINUTES.with(|d| *d.borrow());
+    if low_memory && !DISCARD_ON_LOW_MEMORY.with(|d| *d.borrow()) { return; }
+    if !low_memory && minutes == 0 { return; }
+    let idle = std::time::Duration::from_secs(u64::from(minutes) * 60);
+    for webview in all_webviews() {
+        if low_memory || last_used(&webview).elapsed() >= idle {
+            discard_tab(&webview);
+        }
+    }
+}
+
+// Tabs of one window, most recently used first
+fn mru_tabs(notebook: &Notebook) -> Vec<WebView> {
+    let mut tabs: Vec<WebView> = TAB_MRU.with(|m| m.borrow().iter().filter_map(|(w, _)| w.upgrade()).collect());
+    tabs.retain(|wv| notebook.page_num(wv).is_some());
+    for page in (0..notebook.n_pages()).filter_map(|i| notebook.nth_page(Some(i))) {
+        if let Ok(wv) = page.downcast::<WebView>() {
+            if !tabs.contains(&wv) { tabs.push(wv); }
+        }
+    }
+    tabs
+}
+
+fn tab_row(webview: &WebView, markup: Option<(&str, &str)>) -> gtk4::ListBoxRow {
+    let row_box = GtkBox::new(Orientation::Horizontal, 8);
+    row_box.set_margin_start(8);
+    row_box.set_margin_end(8);
+    row_box.set_margin_top(4);
+    row_box.set_margin_bottom(4);
+    let favicon = Image::new();
+    favicon.set_pixel_size(16);
+    favicon.set_valign(gtk4::Align::Center);
+    match webview.favicon() {
+        Some(texture) => favicon.set_paintable(Some(&texture)),
+        None => favicon.set_paintable(Some(&default_favicon())),
+    }
+    row_box.append(&favicon);
+    let title = webview.title().filter(|t| !t.is_empty()).unwrap_or_else(|| "New Tab".into());
+    let uri = webview.uri().unwrap_or_default();
+    let text_box = GtkBox::new(Orientation::Vertical, 2);
+    text_box.set_hexpand(true);
+    let title_lbl = Label::new(None);
+    title_lbl.set_halign(gtk4::Align::Start);
+    title_lbl.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+    title_lbl.set_max_width_chars(60);
+    text_box.append(&title_lbl);
+    match markup {
+        Some((title_markup, uri_markup)) => {
+            title_lbl.set_markup(title_markup);
+            let url_lbl = Label::new(None);
+            url_lbl.set_markup(&format!("<small><span foreground='#888888'>{}</span></small>", uri_markup));
+            url_lbl.set_halign(gtk4::Align::Start);
+            url_lbl.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+            url_lbl.set_max_width_chars(60);
+            text_box.append(&url_lbl);
+        }
+        None => {
+            title_lbl.set_text(&title);
+            text_box.set_tooltip_text(Some(&uri));
+        }
+    }
+    row_box.append(&text_box);
+    let row = gtk4::ListBoxRow::new();
+    row.set_child(Some(&row_box));
+    row
+}
+
+// Subsequence match: consecutive characters and an early start score higher
+fn fuzzy_score(text: &str, query: &str) -> Option<i32> {
+    let text: Vec<char> = text.to_lowercase().chars().collect();
+    let mut score = 0;
+    let mut pos = 0;
+    let mut last: Option<usize> = None;
+    for qc in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = pos + text[pos..].iter().position(|&c| c == qc)?;
+        score += match last {
+            Some(l) if l + 1 == found => 5,
+            None => 3 - (found.min(3) as i32),
+            _ => 1,
+        };
+        last = Some(found);
+        pos = found + 1;
+    }
+    Some(score)
+}
+
+fn switch_to_tab(webview: &WebView) {
+    let Some(bw) = WINDOWS.with(|w| w.borrow().iter().find(|bw| bw.notebook.page_num(webview).is_some()).cloned()) else { return };
+    if let Some(id) = group_of(webview).filter(|id| with_group(*id, |g| g.collapsed).unwrap_or(false)) {
+        set_group_collapsed(&bw.notebook, id, false);
+    }
+    if let Some(n) = bw.notebook.page_num(webview) {
+        bw.notebook.set_current_page(Some(n));
+    }
+    bw.window.present();
+    webview.grab_focus();
+}
+
+// Ctrl+Tab walks tabs in most-recently-used order while Ctrl is held
+fn install_tab_switcher(bw: &BrowserWindow, overlay: &Overlay) {
+    let switcher_list = ListBox::new();
+    switcher_list.set_selection_mode(gtk4::SelectionMode::Single);
+    let switcher = GtkBox::new(Orientation::Vertical, 0);
+    switcher.add_css_class("tab-switcher");
+    switcher.set_halign(gtk4::Align::Center);
+    switcher.set_valign(gtk4::Align::Center);
+    switcher.set_visible(false);
+    switcher.set_can_target(false);
+    switcher.append(&switcher_list);
+    overlay.add_overlay(&switcher);
+    let switcher_css = gtk4::CssProvider::new();
+    switcher_css.load_from_data(
+        ".tab-switcher { background: @theme_bg_color; border: 1px solid @borders; border-radius: 8px; padding: 6px; }"
+    );
+    if let Some(display) = gtk4::gdk::Display::default() {
+        gtk4::style_context_add_provider_for_display(
+            &display, &switcher_css, gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION,
+        );
+    }
+
+    let order: std::rc::Rc<RefCell<Vec<WebView>>> = std::rc::Rc::new(RefCell::new(Vec::new()));
+    let finish = clone!(#[weak] switcher, #[weak] switcher_list, #[strong] order, move |commit: bool| {
+        if !switcher.is_visible() { return; }
+        switcher.set_visible(false);
+        let chosen = switcher_list.selected_row().and_then(|row| order.borrow().get(row.index() as usize).cloned());
+        while let Some(child) = switcher_list.first_child() { switcher_list.remove(&child); }
+        order.borrow_mut().clear();
+        if let Some(webview) = chosen.filter(|_| commit) {
+            switch_to_tab(&webview);
+        }
+    });
+
+    // The switcher stays up while the shortcut's modifiers are held and commits once they are all released
+    let held = gtk4::gdk::ModifierType::CONTROL_MASK | gtk4::gdk::ModifierType::ALT_MASK | gtk4::gdk::ModifierType::SUPER_MASK;
+    for (name, backwards) in [("switch-recent-tab", false), ("switch-recent-tab-backwards", true)] {
+        let action = gtk4::gio::SimpleAction::new(name, None);
+        action.connect_activate(clone!(
+            #[strong(rename_to = notebook)] bw.notebook, #[weak] switcher, #[weak] switcher_list, #[strong] order, #[strong] finish,
+            move |_, _| {
+                if !switcher.is_visible() {
+                    let tabs = mru_tabs(&notebook);
+                    if tabs.len() < 2 { return; }
+                    for wv in &tabs {
+                        switcher_list.append(&tab_row(wv, None));
+                    }
+                    order.replace(tabs);
+                    switcher.set_visible(true);
+                }
+                let n = order.borrow().len() as i32;
+                let current = switcher_list.selected_row().map(|r| r.index()).unwrap_or(0);
+                let next = (current + if backwards { n - 1 } else { 1 }) % n;
+                switcher_list.select_row(switcher_list.row_at_index(next).as_ref());
+                let modifiers = gtk4::gdk::Display::default()
+                    .and_then(|display| display.default_seat())
+                    .and_then(|seat| seat.keyboard())
+                    .map(|keyboard| keyboard.modifier_state())
+                    .unwrap_or_else(gtk4::gdk::ModifierType::empty);
+                if !modifiers.intersects(held) { finish(true); }
+            }
+        ));
+        bw.window.add_action(&action);
+    }
+
+    let keys = gtk4::EventControllerKey::new();
+    keys.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    keys.connect_key_pressed(clone!(#[weak] switcher, #[strong] finish, #[upgrade_or] glib::Propagation::Proceed, move |_, key, _, _| {
+        if key == Key::Escape && switcher.is_visible() {
+            finish(false);
+            return glib::Propagation::Stop;
+        }
+        glib::Propagation::Proceed
+    }));
+    keys.connect_key_released(clone!(#[strong] finish, move |_, key, _, state| {
+        let released = match key {
+            Key::Control_L | Key::Control_R => gtk4::gdk::ModifierType::CONTROL_MASK,
+            Key::Alt_L | Key::Alt_R => gtk4::gdk::ModifierType::ALT_MASK,
+            Key::Super_L | Key::Super_R => gtk4::gdk::ModifierType::SUPER_MASK,
+            _ => return,
+        };
+        if (state & (held - released)).is_empty() { finish(true); }
+    }));
+    bw.window.add_controller(keys);
+    bw.window.connect_is_active_notify(move |window| {
+        if !window.is_active() { finish(false); }
+    });
+}
+
+// Ctrl+Shift+A: fuzzy search the open tabs of every window by title and URL
+fn install_tab_search(bw: &BrowserWindow, parent: &GtkBox) {
+    let search_entry = gtk4::SearchEntry::new();
+    search_entry.set_placeholder_text(Some("Search tabs"));
+    let results = ListBox::new();
+    results.set_selection_mode(gtk4::SelectionMode::Single);
+    let scroll = gtk4::ScrolledWindow::new();
+    scroll.set_hscrollbar_policy(gtk4::PolicyType::Never);
+    scroll.set_propagate_natural_height(true);
+    scroll.set_max_content_height(400);
+    scroll.set_child(Some(&results));
+    let content = GtkBox::new(Orientation::Vertical, 6);
+    content.set_size_request(420, -1);
+    content.append(&search_entry);
+    content.append(&scroll);
+    let popover = gtk4::Popover::new();
+    popover.set_child(Some(&content));
+    popover.set_parent(parent);
+    popover.set_position(gtk4::PositionType::Bottom);
+
+    let matches: std::rc::Rc<RefCell<Vec<WebView>>> = std::rc::Rc::new(RefCell::new(Vec::new()));
+    let private = bw.private;
+    search_entry.connect_search_changed(clone!(#[weak] results, #[strong] matches, move |entry| {
+        while let Some(child) = results.first_child() { results.remove(&child); }
+        let query = entry.text().to_string();
+        // Private and normal windows never see each other's tabs
+        let tabs = all_webviews().into_iter().filter(|wv| is_private(wv) == private);
+        let mut scored: Vec<(i32, WebView)> = tabs.filter_map(|wv| {
+            let title = wv.title().unwrap_or_default().to_string();
+            let uri = wv.uri().unwrap_or_default().to_string();
+            let score = fuzzy_score(&title, &query).max(fuzzy_score(&uri, &query))?;
+            Some((score, wv))
+        }).collect();
+        scored.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+        for (_, wv) in &scored {
+            let title = wv.title().filter(|t| !t.is_empty()).unwrap_or_else(|| "New Tab".into());
+            let uri = wv.uri().unwrap_or_default();
+            let row = tab_row(wv, Some((&highlight_match(&title, &query), &highlight_match(&uri, &query))));
+            results.append(&row);
+        }
+        results.select_row(results.row_at_index(0).as_ref());
+        matches.replace(scored.into_iter().map(|(_, wv)| wv).collect());
+    }));
+    let jump = clone!(#[weak] popover, #[strong] matches, move |index: i32| {
+        let Some(webview) = matches.borrow().get(index as usize).cloned() else { return };
+        popover.popdown();
+        switch_to_tab(&webview);
+    });
+    results.connect_row_activated(clone!(#[strong] jump, move |_, row| jump(row.index())));
+    search_entry.connect_activate(clone!(#[weak] results, #[strong] jump, move |_| {
+        if let Some(row) = results.selected_row() { jump(row.index()); }
+    }));
+    search_entry.connect_stop_search(clone!(#[weak] popover, move |_| popover.popdown()));
+    let nav_keys = gtk4::EventControllerKey::new();
+    nav_keys.connect_key_pressed(clone!(#[weak] results, #[upgrade_or] glib::Propagation::Proceed, move |_, key, _, _| {
+        let step = match key {
+            Key::Down => 1,
+            Key::Up => -1,
+            _ => return glib::Propagation::Proceed,
+        };
+        let current = results.selected_row().map(|r| r.index()).unwrap_or(-1);
+        if let Some(row) = results.row_at_index(current + step) {
+            results.select_row(Some(&row));
+        }
+        glib::Propagation::Stop
+    }));
+    search_entry.add_controller(nav_keys);
+
+    let search_tabs_action = gtk4::gio::SimpleAction::new("search-tabs", None);
+    search_tabs_action.connect_activate(clone!(#[weak] popover, #[weak] search_entry, move |_, _| {
+        search_entry.set_text("");
+        search_entry.emit_by_name::<()>("search-changed", &[]);
+        popover.popup();
+        search_entry.grab_focus();
+    }));
+    bw.window.add_action(&search_tabs_action);
+}
+
+// Full-window grid of this window's tabs; typing filters it and cards drag to reorder
+fn install_tab_overview(bw: &BrowserWindow, overlay: &Overlay) {
+    let search_entry = gtk4::SearchEntry::new();
+    search_entry.set_placeholder_text(Some("Filter tabs"));
+    search_entry.set_halign(gtk4::Align::Center);
+    search_entry.set_width_chars(40);
+    let grid = gtk4::FlowBox::new();
+    grid.set_selection_mode(gtk4::SelectionMode::Single);
+    grid.set_homogeneous(true);
+    grid.set_valign(gtk4::Align::Start);
+    grid.set_column_spacing(12);
+    grid.set_row_spacing(12);
+    grid.set_max_children_per_line(6);
+    let scroll = gtk4::ScrolledWindow::new();
+    scroll.set_hscrollbar_policy(gtk4::PolicyType::Never);
+    scroll.set_vexpand(true);
+    scroll.set_child(Some(&grid));
+    let overview = GtkBox::new(Orientation::Vertical, 12);
+    overview.add_css_class("tab-overview");
+    overview.set_visible(false);
+    overview.append(&search_entry);
+    overview.append(&scroll);
+    overlay.add_overlay(&overview);
+    search_entry.set_key_capture_widget(Some(&overview));
+    let overview_css = gtk4::CssProvider::new();
+    overview_css.load_from_data(
+        ".tab-overview { background: @theme_bg_color; padding: 24px; }
+         .overview-card { border: 1px solid @borders; border-radius: 8px; padding: 6px; }
+         .overview-card picture { border-radius: 4px; background: alpha(@theme_fg_color, 0.05); }"
+    );
+    if let Some(display) = gtk4::gdk::Display::default() {
+        gtk4::style_context_add_provider_for_display(
+            &display, &overview_css, gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION,
+        );
+    }
+
+    let notebook = bw.notebook.clone();
+    let cards: std::rc::Rc<RefCell<Vec<(WebView, gtk4::FlowBoxChild)>>> = std::rc::Rc::new(RefCell::new(Vec::new()));
+    let card_view = clone!(#[strong] cards, move |child: &gtk4::FlowBoxChild| {
+        cards.borrow().iter().find(|(_, c)| c == child).map(|(wv, _)| wv.clone())
+    });
+    grid.set_filter_func(clone!(#[strong] card_view, #[weak] search_entry, #[upgrade_or] true, move |child| {
+        let query = search_entry.text();
+        if query.is_empty() { return true; }
+        card_view(child).is_some_and(|wv| {
+            let title = wv.title().unwrap_or_default();
+            let uri = wv.uri().unwrap_or_default();
+            fuzzy_score(&title, &query).or(fuzzy_score(&uri, &query)).is_some()
+        })
+    }));
+    grid.set_sort_func(clone!(#[strong] card_view, #[weak] notebook, #[upgrade_or] gtk4::Ordering::Equal, move |a, b| {
+        let index = |child| card_view(child).and_then(|wv| notebook.page_num(&wv));
+        index(a).cmp(&index(b)).into()
+    }));
+
+    let close = clone!(#[weak] overview, #[weak] grid, #[weak] notebook, #[strong] cards, move || {
+        if !overview.is_visible() { return; }
+        overview.set_visible(false);
+        for (_, child) in cards.take() { grid.remove(&child); }
+        if let Some(wv) = current_webview(&notebook) { wv.grab_focus(); }
+    });
+    let open = clone!(#[weak] overview, #[weak] grid, #[weak] search_entry, #[weak] notebook, #[strong] cards, move || {
+        for webview in notebook_tabs(&notebook) {
+            let picture = gtk4::Picture::new();
+            picture.set_size_request(240, 150);
+            picture.set_can_shrink(true);
+            picture.set_content_fit(gtk4::ContentFit::ScaleDown);
+            picture.set_paintable(Some(&webview.favicon().unwrap_or_else(default_favicon)));
+            // Discarded tabs have no page to draw and keep their favicon instead
+            if !is_discarded(&webview) {
+                webview.snapshot(
+                    webkit6::SnapshotRegion::Visible, webkit6::SnapshotOptions::NONE, None::<&gtk4::gio::Cancellable>,
+                    clone!(#[weak] picture, move |result| if let Ok(texture) = result {
+                        picture.set_content_fit(gtk4::ContentFit::Cover);
+                        picture.set_paintable(Some(&texture));
+                    }),
+                );
+            }
+            let favicon = Image::new();
+            favicon.set_pixel_size(16);
+            favicon.set_paintable(Some(&webview.favicon().unwrap_or_else(default_favicon)));
+            let title = Label::new(Some(&webview.title().filter(|t| !t.is_empty()).unwrap_or_else(|| "New Tab".into())));
+            title.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+            title.set_max_width_chars(24);
+            title.set_hexpand(true);
+            title.set_xalign(0.0);
+            let close_btn = Button::with_label("×");
+            close_btn.set_has_frame(false);
+            let header = GtkBox::new(Orientation::Horizontal, 6);
+            header.append(&favicon);
+            header.append(&title);
+            header.append(&close_btn);
+            let card = GtkBox::new(Orientation::Vertical, 6);
+            card.add_css_class("overview-card");
+            card.set_tooltip_text(webview.uri().as_deref());
+            card.append(&header);
+            card.append(&picture);
+            let child = gtk4::FlowBoxChild::new();
+            child.set_child(Some(&card));
+
+            close_btn.connect_clicked(clone!(#[weak] webview, #[weak] grid, #[weak] child, #[strong] cards, move |_| {
+                cards.borrow_mut().retain(|(_, c)| c != &child);
+                grid.remove(&child);
+                if let Some(label) = notebook_of(&webview).and_then(|notebook| notebook.tab_label(&webview)) {
+                    let _ = label.activate_action("tabctx.close", None);
+                }
+            }));
+            let drag = gtk4::DragSource::new();
+            drag.set_actions(gtk4::gdk::DragAction::MOVE);
+            drag.connect_prepare(clone!(#[weak] webview, #[upgrade_or] None, move |_, _, _| {
+                Some(gtk4::gdk::ContentProvider::for_value(&webview.to_value()))
+            }));
+            card.add_controller(drag);
+            let drop = gtk4::DropTarget::new(WebView::static_type(), gtk4::gdk::DragAction::MOVE);
+            drop.connect_drop(clone!(#[weak] webview, #[weak] notebook, #[weak] grid, #[upgrade_or] false, move |_, value, _, _| {
+                let Ok(dragged) = value.get::<WebView>() else { return false };
+                let (Some(_), Some(index)) = (notebook.page_num(&dragged), notebook.page_num(&webview)) else { return false };
+                notebook.reorder_child(&dragged, Some(index));
+                grid.invalidate_sort();
+                true
+            }));
+            card.add_controller(drop);
+
+            grid.insert(&child, -1);
+            if is_active_tab(&notebook, &webview) { grid.select_child(&child); }
+            cards.borrow_mut().push((webview, child));
+        }
+        search_entry.set_text("");
+        overview.set_visible(true);
+        search_entry.grab_focus();
+    });
+
+    grid.connect_child_activated(clone!(#[strong] card_view, #[strong] close, move |_, child| {
+        let Some(webview) = card_view(child) else { return };
+        close();
+        switch_to_tab(&webview);
+    }));
+    search_entry.connect_search_changed(clone!(#[weak] grid, move |_| grid.invalidate_filter()));
+    // child_at_index follows the sorted order the grid shows, unlike the insertion order of cards
+    let first_shown = clone!(#[weak] grid, #[upgrade_or] None, move || {
+        (0..).map_while(|i| grid.child_at_index(i)).find(|c| c.is_child_visible())
+    });
+    search_entry.connect_activate(clone!(#[strong] first_shown, #[strong] card_view, #[strong] close, move |_| {
+        let Some(webview) = first_shown().and_then(|child| card_view(&child)) else { return };
+        close();
+        switch_to_tab(&webview);
+    }));
+    search_entry.connect_stop_search(clone!(#[strong] close, move |_| close()));
+    let entry_keys = gtk4::EventControllerKey::new();
+    entry_keys.connect_key_pressed(clone!(#[strong] first_shown, #[weak] grid, #[upgrade_or] glib::Propagation::Proceed, move |_, key, _, _| {
+        if key != Key::Down { return glib::Propagation::Proceed; }
+        if let Some(child) = first_shown() {
+            grid.select_child(&child);
+            child.grab_focus();
+        }
+        glib::Propagation::Stop
+    }));
+    search_entry.add_controller(entry_keys);
+    let keys = gtk4::EventControllerKey::new();
+    keys.connect_key_pressed(clone!(#[strong] close, move |_, key, _, _| {
+        if key != Key::Escape { return glib::Propagation::Proceed; }
+        close();
+        glib::Propagation::Stop
+    }));
+    overview.add_controller(keys);
+
+    let overview_action = gtk4::gio::SimpleAction::new("tab-overview", None);
+    overview_action.connect_activate(clone!(#[weak] overview, move |_, _| {
+        if overview.is_visible() { close() } else { open() }
+    }));
+    bw.window.add_action(&overview_action);
+}
+
+fn window_of(webview: &WebView) -> Option<BrowserWindow> {
+    WINDOWS.with(|w| w.borrow().iter().find(|bw| bw.notebook.page_num(webview).is_some()).cloned())
+}
+
+fn is_private(webview: &WebView) -> bool {
+    window_of(webview).is_some_and(|bw| bw.private)
+}
+
+fn notebook_of(webview: &WebView) -> Option<Notebook> {
+    window_of(webview).map(|bw| bw.notebook)
+}
+
+// Moves the live view, with its history and page state, into another window's tab strip
+fn move_tab(webview: &WebView, target: &BrowserWindow, position: Option<u32>) {
+    let Some(source) = window_of(webview) else { return };
+    if source.window == target.window || source.private != target.private { return; }
+    let Some(label) = source.notebook.tab_label(webview) else { return };
+    source.notebook.detach_tab(webview);
+    let index = target.notebook.insert_page(webview, Some(&label), position);
+    target.notebook.set_current_page(Some(index));
+    target.window.present();
+}
+
+// Copies the back/forward list and scroll position through WebKit's session state, not just the URI
+// `offset` picks the history entry the copy shows: 0 is the current page, -1 the one Back leads to
+fn duplicate_tab(webview: &WebView, offset: i32) -> Option<WebView> {
+    let bw = window_of(webview)?;
+    let index = bw.notebook.page_num(webview)?;
+    let state = webview.session_state()?;
+    // Not a related view, so the copy gets its own web process but keeps the session and container
+    let copy = add_tab(&bw, None, Some(""), container_of(webview).as_deref(), true);
+    copy.restore_session_state(&state);
+    match copy.back_forward_list().and_then(|list| list.nth_item(offset)) {
+        Some(item) => copy.go_to_back_forward_list_item(&item),
+        None => copy.load_uri(&webview.uri().unwrap_or_default()),
+    }
+    bw.notebook.reorder_child(&copy, Some(index + 1));
+    if let Some(id) = group_of(webview) {
+        add_to_group(&bw.notebook, id, std::slice::from_ref(&copy));
+    }
+    Some(copy)
+}
+
+// Opens right after `webview`, in the same container and group; background tabs leave the focus where it was
+fn open_tab_beside(webview: &WebView, uri: &str, foreground: bool) {
+    let Some(bw) = window_of(webview) else { return };
+    let Some(index) = bw.notebook.page_num(webview) else { return };
+    let tab = add_tab(&bw, None, Some(uri), container_of(webview).as_deref(), foreground);
+    bw.notebook.reorder_child(&tab, Some(index + 1));
+    if let Some(id) = group_of(webview) {
+        add_to_group(&bw.notebook, id, std::slice::from_ref(&tab));
+    }
+}
+
+fn fill_window_menu(menu: &gtk4::gio::Menu, bw: &BrowserWindow) {
+    menu.remove_all();
+    menu.append(Some("Move to New Window"), Some("tabctx.open-new-window"));
+    let windows = gtk4::gio::Menu::new();
+    for other in WINDOWS.with(|w| w.borrow().clone()) {
+        if other.window == bw.window || other.private != bw.private { continue; }
+        let title = current_webview(&other.notebook).and_then(|wv| wv.title()).filter(|t| !t.is_empty());
+        let label = format!("{} ({} tabs)", title.as_deref().unwrap_or("New Tab"), other.notebook.n_pages());
+        let item = gtk4::gio::MenuItem::new(Some(&label), None);
+        item.set_action_and_target_value(Some("tabctx.move-to-window"), Some(&other.id.to_variant()));
+        windows.append_item(&item);
+    }
+    if windows.n_items() > 0 { menu.append_submenu(Some("Move to Window"), &windows); }
+}
+
+fn fill_group_menu(menu: &gtk4::gio::Menu, bw: &BrowserWindow, webview: &WebView) {
+    menu.remove_all();
+    if bw.private || is_pinned(webview) { return; }
+    let Some(id) = group_of(webview) else {
+        menu.append(Some("Add to New Group"), Some("tabctx.new-group"));
+        let groups = gtk4::gio::Menu::new();
+        for (id, name) in window_groups(bw) {
+            let item = gtk4::gio::MenuItem::new(Some(&name), None);
+            item.set_action_and_target_value(Some("tabctx.add-to-group"), Some(&id.to_variant()));
+            groups.append_item(&item);
+        }
+        if groups.n_items() > 0 { menu.append_submenu(Some("Add to Group"), &groups); }
+        return;
+    };
+    let collapsed = with_group(id, |g| g.collapsed).unwrap_or(false);
+    menu.append(Some(if collapsed { "Expand Group" } else { "Collapse Group" }), Some("tabctx.toggle-group"));
+    menu.append(Some("Rename Group…"), Some("tabctx.rename-group"));
+    let colors = gtk4::gio::Menu::new();
+    for (name, color) in GROUP_COLORS {
+        let item = gtk4::gio::MenuItem::new(Some(name), None);
+        item.set_action_and_target_value(Some("tabctx.group-color"), Some(&color.to_variant()));
+        colors.append_item(&item);
+    }
+    menu.append_submenu(Some("Group Color"), &colors);
+    let others = gtk4::gio::Menu::new();
+    for (other, name) in window_groups(bw).into_iter().filter(|(other, _)| *other != id) {
+        let item = gtk4::gio::MenuItem::new(Some(&name), None);
+        item.set_action_and_target_value(Some("tabctx.add-to-group"), Some(&other.to_variant()));
+        others.append_item(&item);
+    }
+    if others.n_items() > 0 { menu.append_submenu(Some("Move to Group"), &others); }
+    menu.append(Some("Remove from Group"), Some("tabctx.ungroup"));
+    menu.append(Some("Save Group"), Some("tabctx.save-group"));
+    menu.append(Some("Close Group"), Some("tabctx.close-group"));
+}
+
+fn new_tab_buttons(private: bool) -> (GtkBox, Button) {
+    let new_tab_btn = Button::with_label("+");
+    new_tab_btn.set_has_frame(false);
+    let new_tab_box = GtkBox::new(Orientation::Horizontal, 0);
+    new_tab_box.append(&new_tab_btn);
+    let container_menu = gtk4::gio::Menu::new();
+    let container_btn = gtk4::MenuButton::builder()
+        .has_frame(false)
+        .tooltip_text("New Tab in Container…")
+        .menu_model(&container_menu)
+        .visible(!private)
+        .build();
+    container_btn.set_create_popup_func(clone!(#[weak] container_menu, move |_| {
+        container_menu.remove_all();
+        let section = gtk4::gio::Menu::new();
+        for (name, _) in CONTAINERS.with(|c| c.borrow().clone()) {
+            let item = gtk4::gio::MenuItem::new(Some(&name), None);
+            item.set_action_and_target_value(Some("win.new-container-tab"), Some(&name.to_variant()));
+            section.append_item(&item);
+        }
+        container_menu.append_section(Some("New Tab in Container"), &section);
+        container_menu.append(Some("Manage Containers…"), Some("win.manage-containers"));
+        let saved = gtk4::gio::Menu::new();
+        for (id, name) in SAVED_GROUPS.with(|s| s.borrow().iter().map(|g| (g.id, g.name.clone())).collect::<Vec<_>>()) {
+            let group = gtk4::gio::Menu::new();
+            for (label, action) in [("Open", "win.open-saved-group"), ("Delete", "win.delete-saved-group")] {
+                let item = gtk4::gio::MenuItem::new(Some(label), None);
+                item.set_action_and_target_value(Some(action), Some(&id.to_variant()));
+                group.append_item(&item);
+            }
+            saved.append_submenu(Some(&name), &group);
+        }
+        if saved.n_items() > 0 { container_menu.append_section(Some("Saved Groups"), &saved); }
+    }));
+    new_tab_box.append(&container_btn);
+    (new_tab_box, new_tab_btn)
+}
+
+fn apply_tab_layout(bw: &BrowserWindow) {
+    // url_box is only hidden while the chrome is, and then neither tab layout shows
+    let shown = bw.url_box.is_visible();
+    let vertical = VERTICAL_TABS.with(|v| *v.borrow());
+    bw.notebook.set_show_tabs(shown && !vertical);
+    bw.tab_sidebar.set_visible(shown && vertical);
+    if let Some(separator) = bw.tab_sidebar.next_sibling() {
+        separator.set_visible(shown && vertical);
+    }
+}
+
+fn is_kiosk() -> bool {
+    KIOSK.with(|k| *k.borrow())
+}
+
+// Kiosk windows never get their chrome back
+fn set_chrome_visible(bw: &BrowserWindow, visible: bool) {
+    let visible = visible && !is_kiosk();
+    bw.url_box.set_visible(visible);
+    bw.progress_bar.set_visible(visible);
+    apply_tab_layout(bw);
+}
+
+fn create_browser_window(app: &Application, related_view: Option<&WebView>, private: bool) -> WebView {
+    add_tab(&browser_window(app, private), related_view, None, None, true)
+}
+
+// A window with no tabs yet, for tabs that are about to be opened or moved into it
+fn browser_window(app: &Application, private: bool) -> BrowserWindow {
+    let title = if private { "rug — private browsing" } else { "rug" };
     let window = ApplicationWindow::builder()
         .application(app)
         .default_width(800)
@@ -698,7 +3694,9 @@ fn create_browser_window(app: &Application, related_view: Option<&WebView>) -> W
     completion_box.append(&completion_list);
     let completion_css = gtk4::CssProvider::new();
     completion_css.load_from_data(
-        ".completion-dropdown { background: @theme_bg_color; border: 1px solid @borders; border-radius: 0 0 4px 4px; }"
+        ".completion-dropdown { background: @theme_bg_color; border: 1px solid @borders; border-radius: 0 0 4px 4px; }
+         .tab-selected { background: alpha(@theme_selected_bg_color, 0.3); border-radius: 4px; }
+         .discarded { opacity: 0.5; }"
     );
     if let Some(display) = gtk4::gdk::Display::default() {
         gtk4::style_context_add_provider_for_display(
@@ -706,7 +3704,7 @@ fn create_browser_window(app: &Application, related_view: Option<&WebView>) -> W
         );
     }
 
-    if PRIVATE.with(|p| *p.borrow()) {
+    if private {
         let private_css = gtk4::CssProvider::new();
         private_css.load_from_data(".private-favicon { filter: invert(1); }");
         if let Some(display) = gtk4::gdk::Display::default() {
@@ -717,6 +3715,7 @@ fn create_browser_window(app: &Application, related_view: Option<&WebView>) -> W
     }
 
     let bar_focused = std::rc::Rc::new(std::cell::Cell::new(false));
+    let inline_completing = std::rc::Rc::new(std::cell::Cell::new(false));
 
     let focus_ctrl = gtk4::EventControllerFocus::new();
     focus_ctrl.connect_enter(clone!(#[weak] url_bar, #[strong] bar_focused, move |_| {
@@ -746,7 +3745,24 @@ fn create_browser_window(app: &Application, related_view: Option<&WebView>) -> W
     progress_bar.set_hexpand(true);
     progress_bar.set_show_text(false);
 
+    let find_entry = gtk4::SearchEntry::new();
+    find_entry.set_placeholder_text(Some("Find in page"));
+    find_entry.set_width_chars(30);
+    let find_previous = Button::with_label("↑");
+    let find_next = Button::with_label("↓");
+    let find_box = GtkBox::new(Orientation::Horizontal, 4);
+    find_box.append(&find_entry);
+    find_box.append(&find_previous);
+    find_box.append(&find_next);
+    let find_bar = gtk4::SearchBar::new();
+    find_bar.set_child(Some(&find_box));
+    find_bar.connect_entry(&find_entry);
+    find_bar.set_show_close_button(true);
+
     let go_button = Button::with_label("Go");
+    let overview_button = Button::with_label("▦");
+    overview_button.set_tooltip_text(Some("Tab Overview"));
+    overview_button.set_action_name(Some("win.tab-overview"));
     let back_button = Button::with_label("←");
     let forward_button = Button::with_label("→");
 
@@ -843,11 +3859,12 @@ fn create_browser_window(app: &Application, related_view: Option<&WebView>) -> W
         }
     ));
     url_bar.add_controller(url_key_ctrl);
+    install_inline_completion(&url_bar, &bar_focused, &inline_completing);
 
     go_button.connect_clicked(clone!(#[weak] notebook, #[weak] url_bar, #[weak] completion_box, move |_| {
         completion_box.set_visible(false);
         if let Some(webview) = current_webview(&notebook) {
-            webview.load_uri(&smart_uri(&url_bar.text()));
+            webview.load_uri(&smart_uri(&url_bar.text(), private));
         }
     }));
 
@@ -863,10 +3880,26 @@ fn create_browser_window(app: &Application, related_view: Option<&WebView>) -> W
         }
     }));
 
+    // Middle-clicking Back or Forward opens that entry in a background copy of the tab
+    for (button, offset) in [(&back_button, -1), (&forward_button, 1)] {
+        let middle_click = gtk4::GestureClick::new();
+        middle_click.set_button(gtk4::gdk::BUTTON_MIDDLE);
+        middle_click.connect_released(clone!(#[weak] notebook, #[weak] button, move |gesture, _, _, _| {
+            gesture.set_state(gtk4::EventSequenceState::Claimed);
+            let Some(webview) = current_webview(&notebook).filter(|_| button.is_sensitive()) else { return };
+            if duplicate_tab(&webview, offset).is_some() {
+                if let Some(n) = notebook.page_num(&webview) { notebook.set_current_page(Some(n)); }
+            }
+        }));
+        button.add_controller(middle_click);
+    }
+
     url_bar.connect_changed(clone!(
         #[weak] completion_box, #[weak] completion_list,
-        #[weak] url_bar, #[weak] notebook, #[weak] container, #[strong] bar_focused,
+        #[weak] url_bar, #[weak] notebook, #[weak] container, #[strong] bar_focused, #[strong] inline_completing,
         move |_| {
+            // The dropdown keeps matching what was typed, not the inline completion after it
+            if inline_completing.get() { return; }
             while let Some(child) = completion_list.first_child() {
                 completion_list.remove(&child);
             }
@@ -946,42 +3979,201 @@ fn create_browser_window(app: &Application, related_view: Option<&WebView>) -> W
             }
             completion_box.set_visible(true);
         }
-    ));
+    ));
+
+    // "+" button in tab bar for new blank tabs, with a container picker beside it
+    let (new_tab_box, new_tab_btn) = new_tab_buttons(private);
+    notebook.set_action_widget(&new_tab_box, gtk4::PackType::End);
+
+    // Vertical tab sidebar; the notebook keeps the webviews and just hides its tabs
+    let tab_list = ListBox::new();
+    tab_list.add_css_class("navigation-sidebar");
+    tab_list.set_selection_mode(gtk4::SelectionMode::Single);
+    let tab_scroll = gtk4::ScrolledWindow::new();
+    tab_scroll.set_hscrollbar_policy(gtk4::PolicyType::Never);
+    tab_scroll.set_vexpand(true);
+    tab_scroll.set_child(Some(&tab_list));
+    let sidebar_collapse = gtk4::ToggleButton::new();
+    sidebar_collapse.set_has_frame(false);
+    sidebar_collapse.set_active(SIDEBAR_COLLAPSED.with(|c| *c.borrow()));
+    let (sidebar_new_tab_box, sidebar_new_tab_btn) = new_tab_buttons(private);
+    let sidebar_header = GtkBox::new(Orientation::Horizontal, 0);
+    sidebar_header.append(&sidebar_collapse);
+    sidebar_header.append(&sidebar_new_tab_box);
+    let tab_sidebar = GtkBox::new(Orientation::Vertical, 0);
+    tab_sidebar.append(&sidebar_header);
+    tab_sidebar.append(&tab_scroll);
+    let collapse_sidebar = clone!(#[weak] tab_sidebar, #[weak] sidebar_header, move |collapse: &gtk4::ToggleButton| {
+        let collapsed = collapse.is_active();
+        collapse.set_label(if collapsed { "»" } else { "«" });
+        collapse.set_tooltip_text(Some(if collapsed { "Expand Tabs" } else { "Collapse Tabs" }));
+        sidebar_header.set_orientation(if collapsed { Orientation::Vertical } else { Orientation::Horizontal });
+        tab_sidebar.set_size_request(if collapsed { -1 } else { 220 }, -1);
+    });
+    collapse_sidebar(&sidebar_collapse);
+    sidebar_collapse.connect_toggled(move |collapse| {
+        collapse_sidebar(collapse);
+        let collapsed = collapse.is_active();
+        // Other windows follow; their own toggles find the setting already changed
+        if SIDEBAR_COLLAPSED.with(|c| c.replace(collapsed)) == collapsed { return; }
+        save_settings();
+        for bw in WINDOWS.with(|w| w.borrow().clone()) {
+            bw.sidebar_collapse.set_active(collapsed);
+        }
+    });
+    tab_list.connect_row_activated(clone!(#[weak] notebook, move |_, row| {
+        notebook.set_current_page(Some(row.index() as u32));
+    }));
+    // Pinned tabs always stay together at the left
+    notebook.connect_page_reordered(|notebook, child, index| {
+        let Some(webview) = child.downcast_ref::<WebView>() else { return };
+        let pinned = pinned_count(notebook);
+        if is_pinned(webview) && index >= pinned {
+            notebook.reorder_child(webview, Some(pinned - 1));
+        } else if !is_pinned(webview) && index < pinned {
+            notebook.reorder_child(webview, Some(pinned));
+        } else {
+            regroup_after_move(notebook, webview, index);
+        }
+    });
 
-    // "+" button in tab bar for new blank tabs
-    let new_tab_btn = Button::with_label("+");
-    new_tab_btn.set_has_frame(false);
-    new_tab_btn.connect_clicked(clone!(
-        #[weak] notebook, #[weak] window, #[weak] url_bar,
-        #[weak] back_button, #[weak] forward_button, #[weak] progress_bar, #[strong] app,
-        move |_| {
-            add_tab(&notebook, &window, &url_bar, &back_button, &forward_button, &progress_bar, &app, None, None);
+    // Tabs arrive here when opened, or when dragged or moved over from another window
+    notebook.set_group_name(Some(if private { "rug-private-tabs" } else { "rug-tabs" }));
+    notebook.connect_page_added(|notebook, child, _| {
+        if let Some(label) = notebook.tab_label(child) {
+            let _ = label.activate_action("tabctx.attach", None);
         }
-    ));
-    notebook.set_action_widget(&new_tab_btn, gtk4::PackType::End);
+    });
+    notebook.connect_page_removed(clone!(#[weak] window, move |notebook, _, _| {
+        if !window.is_visible() { return; }
+        refresh_groups(notebook);
+        refresh_audio_indicator(notebook);
+        // A window whose last tab was closed or dragged away goes with it
+        if notebook.n_pages() == 0 {
+            glib::idle_add_local_once(clone!(#[weak] window, #[weak] notebook, move || {
+                if notebook.n_pages() == 0 { window.close(); }
+            }));
+        }
+    }));
+    // Dropping a dragged tab outside any tab strip detaches it into a window of its own
+    notebook.connect_create_window(clone!(#[strong] app, move |_, _| {
+        Some(browser_window(&app, private).notebook)
+    }));
+    notebook.connect_switch_page(clone!(#[weak] tab_list, #[weak] find_bar, move |notebook, page, page_num| {
+        // The tab being left was in use until now
+        if let Some(previous) = current_webview(notebook) {
+            touch_mru(&previous);
+            if let Some(fc) = previous.find_controller() { fc.search_finish(); }
+        }
+        find_bar.set_search_mode(false);
+        if let Some(webview) = page.downcast_ref::<WebView>() {
+            touch_mru(webview);
+            restore_discarded(webview);
+        }
+        tab_list.select_row(tab_list.row_at_index(page_num as i32).as_ref());
+    }));
+
+    let permission_bar = GtkBox::new(Orientation::Vertical, 0);
+    let audio_button = audio_menu_button(&notebook);
 
-    let webview = add_tab(&notebook, &window, &url_bar, &back_button, &forward_button, &progress_bar, app, related_view, None);
+    let bw = BrowserWindow {
+        id: glib::uuid_string_random().to_string(),
+        app: app.clone(),
+        window: window.clone(),
+        notebook: notebook.clone(),
+        url_bar: url_bar.clone(),
+        back_button: back_button.clone(),
+        forward_button: forward_button.clone(),
+        progress_bar: progress_bar.clone(),
+        permission_bar: permission_bar.clone(),
+        tab_sidebar: tab_sidebar.clone(),
+        tab_list: tab_list.clone(),
+        sidebar_collapse: sidebar_collapse.clone(),
+        audio_button: audio_button.clone(),
+        find_bar: find_bar.clone(),
+        find_entry: find_entry.clone(),
+        closing: std::rc::Rc::new(std::cell::Cell::new(false)),
+        url_box: url_box.clone(),
+        was_fullscreen: std::rc::Rc::new(std::cell::Cell::new(false)),
+        private,
+    };
+    WINDOWS.with(|w| w.borrow_mut().push(bw.clone()));
+    window.connect_destroy(|window| {
+        WINDOWS.with(|w| w.borrow_mut().retain(|bw| &bw.window != window));
+    });
+    // The window goes once its last tab has agreed to close
+    window.connect_close_request(clone!(#[strong] bw, move |_| {
+        if bw.notebook.n_pages() == 0 { return glib::Propagation::Proceed; }
+        confirm_close_window(&bw);
+        glib::Propagation::Stop
+    }));
+
+    for button in [&new_tab_btn, &sidebar_new_tab_btn] {
+        button.connect_clicked(clone!(#[strong] bw, move |_| {
+            add_tab(&bw, None, None, None, true);
+        }));
+    }
+    apply_tab_layout(&bw);
+
+    let container_tab_action = gtk4::gio::SimpleAction::new("new-container-tab", Some(glib::VariantTy::STRING));
+    container_tab_action.connect_activate(clone!(#[strong] bw, move |_, name| {
+        let name = name.and_then(|n| n.get::<String>());
+        add_tab(&bw, None, None, name.as_deref(), true);
+    }));
+    window.add_action(&container_tab_action);
+    let manage_containers_action = gtk4::gio::SimpleAction::new("manage-containers", None);
+    manage_containers_action.connect_activate(clone!(#[strong] bw, move |_, _| {
+        add_tab(&bw, None, Some("rug://settings#containers"), None, true);
+    }));
+    window.add_action(&manage_containers_action);
+
+    let open_saved_group_action = gtk4::gio::SimpleAction::new("open-saved-group", Some(glib::VariantTy::UINT32));
+    open_saved_group_action.connect_activate(clone!(#[strong] bw, move |_, id| {
+        let Some(id) = id.and_then(|id| id.get::<u32>()) else { return };
+        let Some((name, color, tabs)) = SAVED_GROUPS.with(|s| s.borrow().iter().find(|g| g.id == id).map(|g| {
+            (g.name.clone(), g.color.clone(), g.tabs.iter().map(|t| (t.uri.clone(), t.container.clone())).collect::<Vec<_>>())
+        })) else { return };
+        open_group(&bw, &name, &color, false, &tabs);
+    }));
+    window.add_action(&open_saved_group_action);
+    let delete_saved_group_action = gtk4::gio::SimpleAction::new("delete-saved-group", Some(glib::VariantTy::UINT32));
+    delete_saved_group_action.connect_activate(|_, id| {
+        let Some(id) = id.and_then(|id| id.get::<u32>()) else { return };
+        SAVED_GROUPS.with(|s| s.borrow_mut().retain(|g| g.id != id));
+        save_settings();
+    });
+    window.add_action(&delete_saved_group_action);
+
+    install_window_actions(&bw, &bar_focused, &completion_box);
+    install_find_bar(&bw, &find_previous, &find_next);
 
     let ev_ctrl = gtk4::EventControllerKey::new();
-    ev_ctrl.connect_key_pressed(clone!(#[strong] notebook, move |_, key, _, _| {
-        match key {
-            Key::Escape => { std::process::exit(0); }
-            Key::F5 => {
-                if let Some(wv) = current_webview(&notebook) { wv.reload(); }
+    ev_ctrl.connect_key_pressed(clone!(#[weak] window, #[upgrade_or] glib::Propagation::Proceed, move |_, key, _, state| {
+        match page_first_action(key, state) {
+            Some(action) => {
+                let _ = WidgetExt::activate_action(&window, &action, None);
+                glib::Propagation::Stop
             }
-            _ => (),
+            None => glib::Propagation::Proceed,
         }
-        glib::Propagation::Proceed
     }));
 
     url_box.append(&back_button);
     url_box.append(&forward_button);
     url_box.append(&url_bar);
+    url_box.append(&audio_button);
     url_box.append(&go_button);
+    url_box.append(&overview_button);
 
     container.append(&url_box);
     container.append(&progress_bar);
-    container.append(&notebook);
+    container.append(&permission_bar);
+    container.append(&find_bar);
+    let tabs_and_pages = GtkBox::new(Orientation::Horizontal, 0);
+    tabs_and_pages.append(&tab_sidebar);
+    tabs_and_pages.append(&gtk4::Separator::new(Orientation::Vertical));
+    tabs_and_pages.append(&notebook);
+    container.append(&tabs_and_pages);
     container.set_hexpand(true);
     container.set_vexpand(true);
 
@@ -990,30 +4182,372 @@ fn create_browser_window(app: &Application, related_view: Option<&WebView>) -> W
     window_overlay.add_overlay(&completion_box);
     window.set_child(Some(&window_overlay));
     window.add_controller(ev_ctrl);
+    install_tab_switcher(&bw, &window_overlay);
+    install_tab_search(&bw, &url_box);
+    install_tab_overview(&bw, &window_overlay);
+    if is_kiosk() {
+        set_chrome_visible(&bw, false);
+        bw.was_fullscreen.set(true);
+        window.fullscreen();
+    }
     window.present();
 
-    webview
+    bw
+}
+
+// Completes the typed text inline as a selection; Tab or Right accepts it, Backspace drops it
+fn install_inline_completion(url_bar: &Entry, bar_focused: &std::rc::Rc<std::cell::Cell<bool>>, completing: &std::rc::Rc<std::cell::Cell<bool>>) {
+    let typed = std::rc::Rc::new(RefCell::new(String::new()));
+    let composing = std::rc::Rc::new(std::cell::Cell::new(false));
+    if let Some(text) = url_bar.delegate().and_downcast::<gtk4::Text>() {
+        text.connect_preedit_changed(clone!(#[strong] composing, move |_, preedit| composing.set(!preedit.is_empty())));
+    }
+    // Whether the selection is exactly the completed part
+    let active = clone!(#[strong] typed, move |entry: &Entry| {
+        let start = typed.borrow().chars().count() as i32;
+        let end = entry.text().chars().count() as i32;
+        start < end && entry.selection_bounds() == Some((start, end))
+    });
+
+    url_bar.connect_changed(clone!(
+        #[strong] typed, #[strong] composing, #[strong] completing, #[strong] bar_focused,
+        move |entry| {
+            if completing.get() { return; }
+            let text = entry.text().to_string();
+            let previous = typed.replace(text.clone());
+            // Only typing onto the text completes; deleting, Backspace included, and page loads never do
+            let extended = text.len() > previous.len() && text.starts_with(previous.as_str());
+            if composing.get() || !bar_focused.get() || !extended { return; }
+            let Some(completed) = inline_completion(&text) else { return };
+            // Runs once the cursor has moved past the inserted text
+            // Checked against the text this completion was made for, in case more was typed before it ran
+            glib::idle_add_local_once(clone!(#[weak] entry, #[strong] completing, move || {
+                let start = text.chars().count() as i32;
+                if entry.text() != text || !completed.starts_with(&text) || entry.position() != start { return; }
+                let Some(rest) = completed.get(text.len()..) else { return };
+                let mut position = start;
+                completing.set(true);
+                entry.insert_text(rest, &mut position);
+                entry.select_region(start, -1);
+                completing.set(false);
+            }));
+        }
+    ));
+
+    let keys = gtk4::EventControllerKey::new();
+    keys.set_propagation_phase(gtk4::PropagationPhase::Capture);
+    keys.connect_key_pressed(clone!(#[weak] url_bar, #[strong] typed, #[upgrade_or] glib::Propagation::Proceed, move |_, key, _, state| {
+        if !state.is_empty() { return glib::Propagation::Proceed; }
+        match key {
+            Key::Tab | Key::Right | Key::KP_Right if active(&url_bar) => {
+                typed.replace(url_bar.text().to_string());
+                url_bar.set_position(-1);
+                glib::Propagation::Stop
+            }
+            Key::BackSpace if active(&url_bar) => {
+                let start = typed.borrow().chars().count() as i32;
+                url_bar.delete_text(start, -1);
+                glib::Propagation::Stop
+            }
+            _ => glib::Propagation::Proceed,
+        }
+    }));
+    url_bar.add_controller(keys);
+}
+
+fn install_window_actions(bw: &BrowserWindow, bar_focused: &std::rc::Rc<std::cell::Cell<bool>>, completion_box: &GtkBox) {
+    let window = &bw.window;
+    let add = |name: &str, activate: Box<dyn Fn(&BrowserWindow)>| {
+        let action = gtk4::gio::SimpleAction::new(name, None);
+        action.connect_activate(clone!(#[strong] bw, move |_, _| activate(&bw)));
+        window.add_action(&action);
+    };
+    let with_tab = |f: fn(&WebView)| -> Box<dyn Fn(&BrowserWindow)> {
+        Box::new(move |bw: &BrowserWindow| if let Some(wv) = current_webview(&bw.notebook) { f(&wv) })
+    };
+
+    add("new-tab", Box::new(|bw| { add_tab(bw, None, None, None, true); }));
+    add("close-tab", with_tab(|wv| {
+        if let Some(label) = notebook_of(wv).and_then(|notebook| notebook.tab_label(wv)) {
+            let _ = label.activate_action("tabctx.close", None);
+        }
+    }));
+    add("new-private-window", Box::new(|bw| { create_browser_window(&bw.app, None, true); }));
+    add("duplicate-tab", with_tab(|wv| { duplicate_tab(wv, 0); }));
+    add("focus-url", Box::new(|bw| { bw.url_bar.grab_focus(); }));
+    add("back", Box::new(|bw| bw.back_button.emit_clicked()));
+    add("forward", Box::new(|bw| bw.forward_button.emit_clicked()));
+    add("reload", with_tab(|wv| wv.reload()));
+    add("hard-reload", with_tab(|wv| wv.reload_bypass_cache()));
+    add("stop", Box::new(clone!(#[strong] bar_focused, #[weak] completion_box, move |bw| {
+        let Some(wv) = current_webview(&bw.notebook) else { return };
+        if bar_focused.get() {
+            // Put back the page's address and hand the keyboard back to the page
+            completion_box.set_visible(false);
+            bw.url_bar.set_text(&wv.uri().unwrap_or_default());
+            wv.grab_focus();
+        } else if wv.is_loading() {
+            wv.stop_loading();
+        }
+    })));
+    add("find", Box::new(|bw| {
+        bw.find_bar.set_search_mode(true);
+        bw.find_entry.grab_focus();
+        bw.find_entry.select_region(0, -1);
+    }));
+    add("zoom-in", with_tab(|wv| wv.set_zoom_level((wv.zoom_level() * 1.1).min(5.0))));
+    add("zoom-out", with_tab(|wv| wv.set_zoom_level((wv.zoom_level() / 1.1).max(0.3))));
+    add("zoom-reset", with_tab(|wv| wv.set_zoom_level(1.0)));
+    add("next-tab", Box::new(|bw| {
+        let n = bw.notebook.n_pages();
+        if let Some(page) = bw.notebook.current_page().filter(|_| n > 0) {
+            bw.notebook.set_current_page(Some((page + 1) % n));
+        }
+    }));
+    // Tab 9 always means the last tab
+    let go_to_tab = gtk4::gio::SimpleAction::new("go-to-tab", Some(glib::VariantTy::INT32));
+    go_to_tab.connect_activate(clone!(#[weak(rename_to = notebook)] bw.notebook, move |_, number| {
+        let Some(number) = number.and_then(|n| n.get::<i32>()).and_then(|n| u32::try_from(n).ok()).filter(|n| (1..=9).contains(n)) else { return };
+        let n = notebook.n_pages();
+        let page = if number == 9 { n.saturating_sub(1) } else { number - 1 };
+        if page < n { notebook.set_current_page(Some(page)); }
+    }));
+    window.add_action(&go_to_tab);
+    add("fullscreen", Box::new(|bw| {
+        if bw.window.is_fullscreen() { bw.window.unfullscreen() } else { bw.window.fullscreen() }
+    }));
+    add("previous-tab", Box::new(|bw| {
+        let n = bw.notebook.n_pages();
+        if let Some(page) = bw.notebook.current_page().filter(|_| n > 0) {
+            bw.notebook.set_current_page(Some((page + n - 1) % n));
+        }
+    }));
+}
+
+fn install_find_bar(bw: &BrowserWindow, previous: &Button, next: &Button) {
+    let find_options = webkit6::FindOptions::CASE_INSENSITIVE | webkit6::FindOptions::WRAP_AROUND;
+    let controller = clone!(#[weak(rename_to = notebook)] bw.notebook, #[upgrade_or] None, move || {
+        current_webview(&notebook).and_then(|wv| wv.find_controller())
+    });
+    bw.find_entry.connect_search_changed(clone!(#[strong] controller, move |entry| {
+        let Some(fc) = controller() else { return };
+        entry.remove_css_class("error");
+        if entry.text().is_empty() {
+            fc.search_finish();
+        } else {
+            fc.search(&entry.text(), find_options.bits(), u32::MAX);
+        }
+    }));
+    let search_next = clone!(#[strong] controller, move || if let Some(fc) = controller() { fc.search_next() });
+    let search_previous = clone!(#[strong] controller, move || if let Some(fc) = controller() { fc.search_previous() });
+    bw.find_entry.connect_activate(clone!(#[strong] search_next, move |_| search_next()));
+    bw.find_entry.connect_next_match(clone!(#[strong] search_next, move |_| search_next()));
+    bw.find_entry.connect_previous_match(clone!(#[strong] search_previous, move |_| search_previous()));
+    next.connect_clicked(move |_| search_next());
+    previous.connect_clicked(move |_| search_previous());
+    bw.find_bar.connect_search_mode_enabled_notify(move |bar| {
+        if bar.is_search_mode() { return; }
+        if let Some(fc) = controller() { fc.search_finish(); }
+    });
+}
+
+// Lets the page run its beforeunload handlers; the view's close signal does the removal
+fn close_tab(webview: &WebView) {
+    if let Some(bw) = window_of(webview) { bw.closing.set(false); }
+    webview.try_close();
+}
+
+fn confirm_close_window(bw: &BrowserWindow) {
+    let tabs = notebook_tabs(&bw.notebook);
+    let pending = std::rc::Rc::new(std::cell::Cell::new(tabs.len()));
+    let unsaved = std::rc::Rc::new(std::cell::Cell::new(0u32));
+    let ask = clone!(#[strong] bw, #[strong] unsaved, move || {
+        let tab_count = bw.notebook.n_pages();
+        let downloads = ACTIVE_DOWNLOADS.with(|d| *d.borrow());
+        let confirm = CONFIRM_CLOSE.with(|c| *c.borrow());
+        let mut reasons = Vec::new();
+        if confirm && tab_count > CONFIRM_CLOSE_TABS.with(|c| *c.borrow()) {
+            reasons.push(format!("{} tabs are open.", tab_count));
+        }
+        if confirm && downloads > 0 {
+            reasons.push(match downloads {
+                1 => "A download is in progress.".to_string(),
+                n => format!("{} downloads are in progress.", n),
+            });
+        }
+        match unsaved.get() {
+            0 => {}
+            1 => reasons.push("A page has unsaved changes in a form.".to_string()),
+            n => reasons.push(format!("{} pages have unsaved changes in forms.", n)),
+        }
+        if reasons.is_empty() {
+            close_window_tabs(&bw);
+        } else {
+            show_close_confirmation(&bw, &reasons);
+        }
+    });
+    if tabs.is_empty() { ask(); }
+    for webview in tabs {
+        let done = std::rc::Rc::new(std::cell::Cell::new(false));
+        let answered = clone!(#[strong] pending, #[strong] unsaved, #[strong] ask, move |dirty: bool| {
+            if done.replace(true) { return; }
+            if dirty { unsaved.set(unsaved.get() + 1); }
+            pending.set(pending.get() - 1);
+            if pending.get() == 0 { ask(); }
+        });
+        // A hung or suspended page must not keep the window open, so silence counts as no unsaved changes
+        glib::timeout_add_local_once(std::time::Duration::from_secs(2), clone!(#[strong] answered, move || answered(false)));
+        webview.evaluate_javascript("window.rugUnsavedForm === true", Some("rug"), None, gtk4::gio::Cancellable::NONE, move |result| {
+            answered(result.is_ok_and(|value| value.to_boolean()));
+        });
+    }
+}
+
+// The close handler moves on to the next tab once this one is gone
+fn close_window_tabs(bw: &BrowserWindow) {
+    bw.closing.set(true);
+    if let Some(webview) = notebook_tabs(&bw.notebook).into_iter().next() {
+        webview.try_close();
+    }
+}
+
+fn show_close_confirmation(bw: &BrowserWindow, reasons: &[String]) {
+    let dialog = gtk4::Window::builder()
+        .title("Close Window?")
+        .transient_for(&bw.window)
+        .modal(true)
+        .resizable(false)
+        .default_width(360)
+        .build();
+    let content = GtkBox::new(Orientation::Vertical, 8);
+    content.set_margin_start(12);
+    content.set_margin_end(12);
+    content.set_margin_top(12);
+    content.set_margin_bottom(12);
+    for reason in reasons {
+        let label = Label::new(Some(reason));
+        label.set_wrap(true);
+        label.set_xalign(0.0);
+        content.append(&label);
+    }
+    let dont_ask = gtk4::CheckButton::with_label("Don't ask again");
+    let buttons = GtkBox::new(Orientation::Horizontal, 8);
+    buttons.set_halign(gtk4::Align::End);
+    let cancel = Button::with_label("Cancel");
+    let close = Button::with_label("Close Window");
+    close.add_css_class("destructive-action");
+    buttons.append(&cancel);
+    buttons.append(&close);
+    content.append(&dont_ask);
+    content.append(&buttons);
+    dialog.set_child(Some(&content));
+    dialog.set_default_widget(Some(&close));
+
+    close.connect_clicked(clone!(#[weak] dialog, #[weak] dont_ask, #[strong] bw, move |_| {
+        if dont_ask.is_active() {
+            CONFIRM_CLOSE.with(|c| *c.borrow_mut() = false);
+            save_settings();
+        }
+        dialog.close();
+        close_window_tabs(&bw);
+    }));
+    cancel.connect_clicked(clone!(#[weak] dialog, move |_| dialog.close()));
+    dialog.present();
+}
+
+fn mark_find_result(fc: &webkit6::FindController, found: bool) {
+    let Some(bw) = fc.web_view().and_then(|wv| window_of(&wv)) else { return };
+    if found { bw.find_entry.remove_css_class("error") } else { bw.find_entry.add_css_class("error") }
+}
+
+fn finish_html(request: &webkit6::URISchemeRequest, html: &str) {
+    let bytes = glib::Bytes::from(html.as_bytes());
+    let stream = gtk4::gio::MemoryInputStream::from_bytes(&bytes);
+    request.finish(&stream, bytes.len() as i64, Some("text/html"));
+}
+
+async fn request_body(request: &webkit6::URISchemeRequest) -> String {
+    let Some(stream) = request.http_body() else { return String::new() };
+    let mut body = Vec::new();
+    while let Ok(bytes) = stream.read_bytes_future(8192, glib::Priority::DEFAULT).await {
+        if bytes.is_empty() { break; }
+        body.extend_from_slice(&bytes);
+    }
+    String::from_utf8_lossy(&body).into_owned()
+}
+
+async fn export_passwords(window: &ApplicationWindow) -> Option<String> {
+    let dialog = gtk4::FileDialog::new();
+    dialog.set_title("Export Passwords");
+    dialog.set_initial_name(Some("rug-passwords.csv"));
+    let path = dialog.save_future(Some(window)).await.ok()?.path()?;
+    Some(match passwords::export_csv(&path) {
+        Ok(count) => format!("Exported {} passwords to {}. This file is not encrypted.", count, path.display()),
+        Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+            format!("Nothing exported: {} already exists. Export again with a new file name.", path.display())
+        }
+        Err(e) => format!("Export failed: {}", e),
+    })
+}
+
+fn passwords_page(params: &std::collections::HashMap<String, String>, posted: bool, notice: Option<String>) -> String {
+    if let Some(origin) = params.get("remove_never") {
+        PASSWORD_NEVER_SAVE.with(|n| n.borrow_mut().retain(|o| o != origin));
+        save_settings();
+    }
+    let unlocked = passwords::is_unlocked();
+    let saved_password = |origin: &str, username: &str| {
+        passwords::logins_for(origin).into_iter().find(|(u, _)| u == username).map(|(_, p)| p)
+    };
+    let mut revealed = None;
+    if unlocked {
+        if let (Some(origin), Some(username)) = (params.get("origin"), params.get("username")) {
+            match params.get("action").map(String::as_str) {
+                // An empty password field means the password stays as it was
+                Some("save") if posted => {
+                    let new_username = params.get("new_username").unwrap_or(username);
+                    let password = params.get("password").filter(|p| !p.is_empty()).cloned().or_else(|| saved_password(origin, username));
+                    if let Some(password) = password {
+                        passwords::update(origin, username, new_username, &password);
+                    }
+                }
+                Some("reveal") if posted => {
+                    revealed = saved_password(origin, username).map(|p| (origin.clone(), username.clone(), p));
+                }
+                Some("delete") => passwords::remove(origin, username),
+                _ => {}
+            }
+        }
+    }
+    // Only the row the user asked to see carries its password into the page
+    let mut logins: Vec<(String, String)> = passwords::credentials().into_iter().map(|(o, u, _)| (o, u)).collect();
+    logins.sort();
+    let mut never = PASSWORD_NEVER_SAVE.with(|n| n.borrow().clone());
+    never.sort();
+    html::passwords(unlocked, &logins, revealed.as_ref(), &never, notice.as_deref(), &page_nonce(), DARK_MODE.with(|d| *d.borrow()))
 }
 
 fn main() {
-    if std::env::args().any(|a| a == "--private") {
-        PRIVATE.with(|i| *i.borrow_mut() = true);
+    let start_private = std::env::args().any(|a| a == "--private");
+    // --kiosk <url>: one fullscreen window on that page, without chrome, shortcuts or rug pages
+    let kiosk = std::env::args().any(|a| a == "--kiosk");
+    let kiosk_url = std::env::args().skip_while(|a| a != "--kiosk").nth(1).filter(|u| !u.starts_with("--"));
+    if kiosk && kiosk_url.is_none() {
+        eprintln!("usage: rug --kiosk <url>");
+        std::process::exit(2);
     }
+    KIOSK.with(|k| *k.borrow_mut() = kiosk_url.is_some());
+    export_pac_config();
 
     let app = Application::builder()
         .application_id("com.computermouth.rug")
         .flags(gtk4::gio::ApplicationFlags::NON_UNIQUE)
         .build();
 
-    app.connect_activate(|app| {
-        let incognito = PRIVATE.with(|i| *i.borrow());
-        if !incognito { load_recent(); }
+    app.connect_activate(move |app| {
+        load_recent();
         load_settings();
-        if incognito {
-            apply_dark_mode(true);
-        } else {
-            apply_dark_mode(DARK_MODE.with(|d| *d.borrow()));
-        }
+        apply_dark_mode(DARK_MODE.with(|d| *d.borrow()));
 
         #[cfg(debug_assertions)]
         let (data_dir, cache_dir) = (
@@ -1025,39 +4559,54 @@ fn main() {
             glib::home_dir().join(".local/share/rug"),
             glib::home_dir().join(".cache/rug"),
         );
-        let ns = if incognito {
-            webkit6::NetworkSession::new_ephemeral()
-        } else {
-            webkit6::NetworkSession::new(
-                Some(&data_dir.to_string_lossy()),
-                Some(&cache_dir.to_string_lossy()),
-            )
-        };
-        if let Some(wdm) = ns.website_data_manager() {
-            wdm.set_favicons_enabled(true);
-        }
-        if !incognito {
-            if let Some(cm) = ns.cookie_manager() {
-                let cookie_file = data_dir.join("cookies.sqlite");
-                cm.set_persistent_storage(
-                    &cookie_file.to_string_lossy(),
-                    webkit6::CookiePersistentStorage::Sqlite,
-                );
-            }
+        let ns = webkit6::NetworkSession::new(
+            Some(&data_dir.to_string_lossy()),
+            Some(&cache_dir.to_string_lossy()),
+        );
+        setup_session(&ns, app);
+        if let Some(cm) = ns.cookie_manager() {
+            let cookie_file = data_dir.join("cookies.sqlite");
+            cm.set_persistent_storage(
+                &cookie_file.to_string_lossy(),
+                webkit6::CookiePersistentStorage::Sqlite,
+            );
         }
         NETWORK_SESSION.with(|s| *s.borrow_mut() = Some(ns));
 
-        let webview = create_browser_window(app, None);
+        let memory_monitor = gtk4::gio::MemoryMonitor::dup_default();
+        memory_monitor.connect_low_memory_warning(|_, _| discard_idle_tabs(true));
+        // Holding the monitor here keeps its warnings coming
+        glib::timeout_add_seconds_local(60, move || {
+            let _ = &memory_monitor;
+            discard_idle_tabs(false);
+            glib::ControlFlow::Continue
+        });
+
+        let webview = match &kiosk_url {
+            Some(url) => add_tab(&browser_window(app, false), None, Some(&smart_uri(url, false)), None, true),
+            None => {
+                load_keybindings(app);
+                create_browser_window(app, None, start_private)
+            }
+        };
+        if !start_private && kiosk_url.is_none() {
+            if let Some(bw) = WINDOWS.with(|w| w.borrow().last().cloned()) {
+                restore_pinned_tabs(&bw);
+                restore_tab_groups(&bw);
+            }
+        }
 
         webview.web_context().unwrap().register_uri_scheme("rug", |request| {
             let html = match request.uri().unwrap_or_default().as_str() {
                 "rug://home" => {
                     let p = recent_pages_snapshot();
                     let top: Vec<(String, String)> = p.iter().take(8).map(|(u, t, _)| (u.clone(), t.clone())).collect();
-                    html::home(&top, &top_domains(16), DARK_MODE.with(|d| *d.borrow()), PRIVATE.with(|i| *i.borrow()))
+                    let private = request.web_view().and_then(|wv| wv.network_session()).is_some_and(|ns| ns.is_ephemeral());
+                    html::home(&top, &top_domains(16), DARK_MODE.with(|d| *d.borrow()), private)
                 }
                 s if s.starts_with("rug://settings") => {
-                    let params = parse_query_params(s);
+                    let mut params = parse_query_params(s);
+                    if !nonce_ok(&params) { params.clear(); }
                     let cleared = params.get("clear").map(|v| v == "1").unwrap_or(false);
                     if cleared { clear_history(); }
                     let cache_cleared = params.get("clear_cache").map(|v| v == "1").unwrap_or(false);
@@ -1075,65 +4624,527 @@ fn main() {
                             apply_dark_mode(dark);
                             let cache = params.get("cache").map(|v| v == "enabled").unwrap_or(true);
                             CACHE_ENABLED.with(|c| *c.borrow_mut() = cache);
+                            let https_only = params.get("https").map(|v| v == "on").unwrap_or(false);
+                            HTTPS_ONLY.with(|h| *h.borrow_mut() = https_only);
+                            if let Some(policy) = params.get("cookies").filter(|p| ["all", "no-third-party", "none"].contains(&p.as_str())) {
+                                COOKIE_POLICY.with(|c| *c.borrow_mut() = policy.clone());
+                            }
+                            let itp = params.get("itp").map(|v| v == "on").unwrap_or(false);
+                            ITP_ENABLED.with(|i| *i.borrow_mut() = itp);
+                            SEND_GPC.with(|g| *g.borrow_mut() = params.get("gpc").is_some_and(|v| v == "on"));
+                            SEND_DNT.with(|d| *d.borrow_mut() = params.get("dnt").is_some_and(|v| v == "on"));
+                            STRIP_TRACKING.with(|t| *t.borrow_mut() = params.get("strip_tracking").map(|v| v == "on").unwrap_or(true));
+                            if let Some(names) = params.get("tracking_params") {
+                                TRACKING_PARAMS.with(|t| *t.borrow_mut() = names.clone());
+                            }
+                            VERTICAL_TABS.with(|v| *v.borrow_mut() = params.get("tab_layout").is_some_and(|l| l == "vertical"));
+                            if let Some(minutes) = params.get("discard_after").and_then(|m| m.trim().parse::<u32>().ok()) {
+                                DISCARD_AFTER_MINUTES.with(|d| *d.borrow_mut() = minutes);
+                            }
+                            DISCARD_ON_LOW_MEMORY.with(|d| *d.borrow_mut() = params.get("discard_low_memory").is_none_or(|v| v == "on"));
+                            CONFIRM_CLOSE.with(|c| *c.borrow_mut() = params.get("confirm_close").is_none_or(|v| v == "on"));
+                            if let Some(tabs) = params.get("confirm_close_tabs").and_then(|t| t.trim().parse::<u32>().ok()) {
+                                CONFIRM_CLOSE_TABS.with(|c| *c.borrow_mut() = tabs);
+                            }
+                            for bw in WINDOWS.with(|w| w.borrow().clone()) {
+                                apply_tab_layout(&bw);
+                            }
+                            let ua = params.get("user_agent").cloned().unwrap_or_default();
+                            CUSTOM_USER_AGENT.with(|u| *u.borrow_mut() = ua);
+                            for wv in all_webviews() {
+                                install_user_scripts(&wv);
+                                apply_user_agent(&wv, &wv.uri().unwrap_or_default());
+                            }
+                            if params.get("proxy_mode").is_some_and(|m| PROXY_MODES.contains(&m.as_str())) {
+                                for (key, setting) in PROXY_FIELDS {
+                                    let value = params.get(key).cloned().unwrap_or_default();
+                                    setting.with(|s| *s.borrow_mut() = value);
+                                }
+                                for ns in all_sessions() {
+                                    apply_proxy_settings(&ns);
+                                }
+                            }
+                            save_settings();
+                        }
+                    }
+                    if let Some(host) = params.get("remove_https_exception") {
+                        HTTPS_EXCEPTIONS.with(|e| e.borrow_mut().retain(|h| h != host));
+                        save_settings();
+                    }
+                    if let (Some(site), Some(rule)) = (params.get("cookie_site"), params.get("cookie_rule")) {
+                        let site = uri_host(&format!("http://{}", site.trim())).unwrap_or_default();
+                        if !site.is_empty() {
+                            COOKIE_EXCEPTIONS.with(|c| {
+                                let mut rules = c.borrow_mut();
+                                rules.retain(|(h, _)| h != &site);
+                                rules.push((site, rule == "allow"));
+                            });
+                            save_settings();
+                        }
+                    }
+                    if let (Some(site), Some(preset)) = (params.get("ua_site"), params.get("ua_preset")) {
+                        let site = uri_host(&format!("http://{}", site.trim())).unwrap_or_default();
+                        if !site.is_empty() && USER_AGENT_PRESETS.iter().any(|(name, _)| name == preset) {
+                            SITE_USER_AGENTS.with(|u| {
+                                let mut sites = u.borrow_mut();
+                                sites.retain(|(h, _)| h != &site);
+                                sites.push((site, preset.clone()));
+                            });
+                            save_settings();
+                        }
+                    }
+                    if let (Some(site), Some(names)) = (params.get("tracking_site"), params.get("tracking_site_params")) {
+                        let site = uri_host(&format!("http://{}", site.trim())).unwrap_or_default();
+                        if !site.is_empty() && !names.trim().is_empty() {
+                            SITE_TRACKING_PARAMS.with(|t| {
+                                let mut sites = t.borrow_mut();
+                                sites.retain(|(h, _)| h != &site);
+                                sites.push((site, names.trim().to_string()));
+                            });
+                            save_settings();
+                        }
+                    }
+                    if let Some(host) = params.get("remove_tracking_site") {
+                        SITE_TRACKING_PARAMS.with(|t| t.borrow_mut().retain(|(h, _)| h != host));
+                        save_settings();
+                    }
+                    if let (Some(name), Some(color)) = (params.get("container_name"), params.get("container_color")) {
+                        let name = name.trim().to_string();
+                        if !name.is_empty() && valid_color(color) {
+                            CONTAINERS.with(|c| {
+                                let mut containers = c.borrow_mut();
+                                match containers.iter_mut().find(|(n, _)| *n == name) {
+                                    Some(existing) => existing.1 = color.clone(),
+                                    None => containers.push((name, color.clone())),
+                                }
+                            });
+                            save_settings();
+                        }
+                    }
+                    if let Some(name) = params.get("remove_container").filter(|n| container_color(n).is_some()) {
+                        // Its tabs go with it, since their storage is about to be deleted
+                        for wv in all_webviews().iter().filter(|wv| container_of(wv).as_ref() == Some(name)) {
+                            close_tab(wv);
+                        }
+                        let (data_dir, cache_dir) = container_dirs(name);
+                        let _ = std::fs::remove_dir_all(data_dir);
+                        let _ = std::fs::remove_dir_all(cache_dir);
+                        CONTAINER_SESSIONS.with(|c| c.borrow_mut().remove(name));
+                        CONTAINER_IDS.with(|c| c.borrow_mut().remove(name));
+                        CONTAINERS.with(|c| c.borrow_mut().retain(|(n, _)| n != name));
+                        CONTAINER_SITES.with(|c| c.borrow_mut().retain(|(_, n)| n != name));
+                        save_settings();
+                    }
+                    if let (Some(site), Some(name)) = (params.get("container_site"), params.get("container_assign")) {
+                        let site = uri_host(&format!("http://{}", site.trim())).unwrap_or_default();
+                        if !site.is_empty() && container_color(name).is_some() {
+                            CONTAINER_SITES.with(|c| {
+                                let mut sites = c.borrow_mut();
+                                sites.retain(|(h, _)| h != &site);
+                                sites.push((site, name.clone()));
+                            });
                             save_settings();
                         }
                     }
+                    if let Some(host) = params.get("remove_container_site") {
+                        CONTAINER_SITES.with(|c| c.borrow_mut().retain(|(h, _)| h != host));
+                        save_settings();
+                    }
+                    if let Some(scheme) = params.get("remove_allowed_scheme") {
+                        ALLOWED_SCHEMES.with(|a| a.borrow_mut().retain(|s| s != scheme));
+                        save_settings();
+                    }
+                    if let Some(host) = params.get("remove_ua_site") {
+                        SITE_USER_AGENTS.with(|u| u.borrow_mut().retain(|(h, _)| h != host));
+                        save_settings();
+                    }
+                    if let Some(host) = params.get("remove_cookie_exception") {
+                        COOKIE_EXCEPTIONS.with(|c| c.borrow_mut().retain(|(h, _)| h != host));
+                        save_settings();
+                    }
+                    if ["engine", "cookie_site", "remove_cookie_exception"].iter().any(|k| params.contains_key(*k)) {
+                        for ns in all_sessions() {
+                            apply_cookie_policy(&ns);
+                            purge_blocked_cookies(&ns);
+                        }
+                    }
+                    if let Some(host) = params.get("remove_tls_exception") {
+                        TLS_EXCEPTIONS.with(|t| t.borrow_mut().retain(|(h, _)| h != host));
+                        save_settings();
+                    }
                     let engine = SEARCH_ENGINE.with(|e| e.borrow().clone());
                     let custom_url = CUSTOM_SEARCH_URL.with(|u| u.borrow().clone());
-                    let dark = DARK_MODE.with(|d| *d.borrow());
-                    let cache_enabled = CACHE_ENABLED.with(|c| *c.borrow());
-                    html::settings(cleared, &engine, &custom_url, dark, cache_enabled, cache_cleared, cookies_cleared)
+                    let https_exceptions = HTTPS_EXCEPTIONS.with(|e| e.borrow().clone());
+                    let cookie_policy = COOKIE_POLICY.with(|c| c.borrow().clone());
+                    let cookie_exceptions = COOKIE_EXCEPTIONS.with(|c| c.borrow().clone());
+                    let [proxy_mode, proxy_http, proxy_https, proxy_socks, proxy_ignore, proxy_pac] =
+                        PROXY_FIELDS.map(|(_, setting)| setting.with(|s| s.borrow().clone()));
+                    let user_agent = CUSTOM_USER_AGENT.with(|u| u.borrow().clone());
+                    let site_user_agents = SITE_USER_AGENTS.with(|u| u.borrow().clone());
+                    let tracking_params = TRACKING_PARAMS.with(|t| t.borrow().clone());
+                    let site_tracking_params = SITE_TRACKING_PARAMS.with(|t| t.borrow().clone());
+                    let allowed_schemes = ALLOWED_SCHEMES.with(|a| a.borrow().clone());
+                    let containers = CONTAINERS.with(|c| c.borrow().clone());
+                    let container_sites = CONTAINER_SITES.with(|c| c.borrow().clone());
+                    let tls_exceptions: Vec<String> = TLS_EXCEPTIONS.with(|t| t.borrow().iter().map(|(h, _)| h.clone()).collect());
+                    html::settings(&html::SettingsPage {
+                        engine: &engine,
+                        custom_url: &custom_url,
+                        dark: DARK_MODE.with(|d| *d.borrow()),
+                        cache_enabled: CACHE_ENABLED.with(|c| *c.borrow()),
+                        https_only: HTTPS_ONLY.with(|h| *h.borrow()),
+                        https_exceptions: &https_exceptions,
+                        tls_exceptions: &tls_exceptions,
+                        allowed_schemes: &allowed_schemes,
+                        cookie_policy: &cookie_policy,
+                        itp: ITP_ENABLED.with(|i| *i.borrow()),
+                        cookie_exceptions: &cookie_exceptions,
+                        proxy_mode: &proxy_mode,
+                        proxy_http: &proxy_http,
+                        proxy_https: &proxy_https,
+                        proxy_socks: &proxy_socks,
+                        proxy_ignore: &proxy_ignore,
+                        proxy_pac: &proxy_pac,
+                        gpc: SEND_GPC.with(|g| *g.borrow()),
+                        dnt: SEND_DNT.with(|d| *d.borrow()),
+                        user_agent: &user_agent,
+                        site_user_agents: &site_user_agents,
+                        strip_tracking: STRIP_TRACKING.with(|t| *t.borrow()),
+                        tracking_params: &tracking_params,
+                        site_tracking_params: &site_tracking_params,
+                        containers: &containers,
+                        container_sites: &container_sites,
+                        vertical_tabs: VERTICAL_TABS.with(|v| *v.borrow()),
+                        discard_after_minutes: DISCARD_AFTER_MINUTES.with(|d| *d.borrow()),
+                        discard_on_low_memory: DISCARD_ON_LOW_MEMORY.with(|d| *d.borrow()),
+                        confirm_close: CONFIRM_CLOSE.with(|c| *c.borrow()),
+                        confirm_close_tabs: CONFIRM_CLOSE_TABS.with(|c| *c.borrow()),
+                        cleared,
+                        cache_cleared,
+                        cookies_cleared,
+                        nonce: &page_nonce(),
+                    })
+                }
+                s if s.starts_with("rug://https-error") => {
+                    let params = parse_query_params(s);
+                    let url = params.get("url").cloned().unwrap_or_default();
+                    let host = uri_host(&url).unwrap_or_default();
+                    if params.get("proceed").map(|v| v == "1").unwrap_or(false) && nonce_ok(&params) && url.starts_with("http://") {
+                        let private = request.web_view().is_some_and(|wv| is_private(&wv));
+                        let exceptions = if private { &PRIVATE_HTTPS_EXCEPTIONS } else { &HTTPS_EXCEPTIONS };
+                        exceptions.with(|e| {
+                            let mut hosts = e.borrow_mut();
+                            if !hosts.contains(&host) { hosts.push(host.clone()); }
+                        });
+                        if !private { save_settings(); }
+                        html::redirect(&url)
+                    } else {
+                        let proceed = format!("rug://https-error?url={}&nonce={}&proceed=1", url_encode(&url), page_nonce());
+                        html::https_error(&url, &host, &proceed, DARK_MODE.with(|d| *d.borrow()))
+                    }
+                }
+                s if s.starts_with("rug://tls-error") => {
+                    let params = parse_query_params(s);
+                    let url = params.get("url").cloned().unwrap_or_default();
+                    let host = uri_host(&url).unwrap_or_default();
+                    let failure = TLS_ERRORS.with(|t| t.borrow().get(&host).cloned());
+                    match (failure, params.get("proceed").map(String::as_str).filter(|_| nonce_ok(&params))) {
+                        (Some((cert, _)), Some(choice @ ("session" | "always"))) => {
+                            if let Some(ns) = request.web_view().and_then(|wv| wv.network_session()) {
+                                ns.allow_tls_certificate_for_host(&cert, &host);
+                            }
+                            // The session allowance above already lasts as long as a private window can
+                            if choice == "always" && !request.web_view().is_some_and(|wv| is_private(&wv)) {
+                                if let Some(pem) = cert.property::<Option<glib::GString>>("certificate-pem") {
+                                    TLS_EXCEPTIONS.with(|t| {
+                                        let mut certs = t.borrow_mut();
+                                        certs.retain(|(h, _)| h != &host);
+                                        certs.push((host.clone(), pem.to_string()));
+                                    });
+                                    save_settings();
+                                }
+                            }
+                            TLS_ERRORS.with(|t| t.borrow_mut().remove(&host));
+                            html::redirect(&url)
+                        }
+                        (Some((cert, errors)), _) => {
+                            let base = format!("rug://tls-error?url={}&nonce={}&proceed=", url_encode(&url), page_nonce());
+                            html::tls_error(
+                                &url, &host, &tls_error_messages(errors), &certificate_chain(&cert),
+                                &format!("{}session", base), &format!("{}always", base),
+                                DARK_MODE.with(|d| *d.borrow()),
+                            )
+                        }
+                        // Without a host the retry would only land back here
+                        (None, _) if host.is_empty() => html::redirect("rug://home"),
+                        // Nothing failed since launch; retry and let the handshake decide
+                        (None, _) => html::redirect(&url),
+                    }
+                }
+                s if s.starts_with("rug://permissions") => {
+                    let params = parse_query_params(s);
+                    if let (Some(origin), Some(kind), true) = (params.get("origin"), params.get("kind"), nonce_ok(&params)) {
+                        match params.get("set").map(String::as_str) {
+                            Some("allow") => remember_permission(origin, kind, true, false),
+                            Some("deny") => remember_permission(origin, kind, false, false),
+                            Some("forget") => {
+                                PERMISSIONS.with(|p| p.borrow_mut().retain(|(o, k, _)| !(o == origin && k == kind)));
+                                save_settings();
+                            }
+                            _ => {}
+                        }
+                        if let Some(ctx) = request.web_view().and_then(|wv| wv.web_context()) {
+                            sync_notification_permissions(&ctx);
+                        }
+                    }
+                    let mut perms = PERMISSIONS.with(|p| p.borrow().clone());
+                    perms.sort();
+                    html::permissions(&perms, &page_nonce(), DARK_MODE.with(|d| *d.borrow()))
+                }
+                s if s.starts_with("rug://passwords") => {
+                    let (request, uri) = (request.clone(), s.to_string());
+                    glib::spawn_future_local(async move {
+                        // Edits and exports are posted so passwords stay out of the URL and history
+                        let posted = request.http_method().is_some_and(|m| m == "POST");
+                        let mut params = if posted {
+                            parse_query_params(&format!("?{}", request_body(&request).await))
+                        } else {
+                            parse_query_params(&uri)
+                        };
+                        if !nonce_ok(&params) { params.clear(); }
+                        passwords::unlock_with_keyring().await;
+                        let window = request.web_view().and_then(|wv| wv.root()).and_downcast::<ApplicationWindow>();
+                        if let (true, Some(window)) = (params.contains_key("unlock"), window.clone()) {
+                            // The page finishes once the master password dialog is done with
+                            let request = request.clone();
+                            with_unlocked_passwords(&window, move |_| finish_html(&request, &passwords_page(&params, posted, None)));
+                            return;
+                        }
+                        let mut notice = None;
+                        if let (true, Some(window)) = (posted && params.contains_key("export") && passwords::is_unlocked(), &window) {
+                            // The plain text copy goes only where the user picks
+                            notice = export_passwords(window).await;
+                        }
+                        finish_html(&request, &passwords_page(&params, posted, notice));
+                    });
+                    return;
                 }
+                "rug://shortcuts" => shortcuts_page(),
                 "rug://private" => {
-                    if let Ok(exe) = std::env::current_exe() {
-                        std::process::Command::new(exe).arg("--private").spawn().ok();
+                    if let Some(app) = gtk4::gio::Application::default().and_downcast::<Application>() {
+                        glib::idle_add_local_once(move || {
+                            create_browser_window(&app, None, true);
+                        });
                     }
-                    String::from("<!DOCTYPE html><html><head><meta http-equiv=\"refresh\" content=\"0;url=rug://home\"></head><body></body></html>")
+                    html::redirect("rug://home")
                 }
                 _ => String::from("<!DOCTYPE html><html><body>Not found</body></html>"),
             };
-            let bytes = glib::Bytes::from(html.as_bytes());
-            let stream = gtk4::gio::MemoryInputStream::from_bytes(&bytes);
-            request.finish(&stream, bytes.len() as i64, Some("text/html"));
+            finish_html(request, &html);
         });
+        // Only rug pages and the browser itself may open rug:// URLs
+        if let Some(sm) = webview.web_context().and_then(|ctx| ctx.security_manager()) {
+            sm.register_uri_scheme_as_local("rug");
+        }
 
-        if !incognito {
-            let cache_model = if CACHE_ENABLED.with(|c| *c.borrow()) {
-                webkit6::CacheModel::WebBrowser
-            } else {
-                webkit6::CacheModel::DocumentViewer
-            };
-            if let Some(ctx) = webview.web_context() {
-                ctx.set_cache_model(cache_model);
-            }
-        }
-
-        let session = webview.network_session().unwrap();
-        session.connect_download_started(clone!(#[strong] app, move |_, download| {
-            download.connect_decide_destination(clone!(#[strong] app, move |download, suggested_filename| {
-                let download = download.clone();
-                let dialog = gtk4::FileDialog::new();
-                dialog.set_initial_name(Some(suggested_filename));
-                let downloads_folder = gtk4::gio::File::for_path(glib::home_dir().join("Downloads"));
-                dialog.set_initial_folder(Some(&downloads_folder));
-                let window = app.active_window();
-                dialog.save(window.as_ref(), gtk4::gio::Cancellable::NONE, move |result| {
-                    match result {
-                        Ok(file) => {
-                            if let Some(path) = file.path() {
-                                download.set_destination(&path.to_string_lossy());
-                            } else {
-                                download.cancel();
-                            }
-                        }
-                        Err(_) => { download.cancel(); }
-                    }
-                });
-                true
-            }));
-        }));
+        let cache_model = if CACHE_ENABLED.with(|c| *c.borrow()) {
+            webkit6::CacheModel::WebBrowser
+        } else {
+            webkit6::CacheModel::DocumentViewer
+        };
+        if let Some(ctx) = webview.web_context() {
+            ctx.set_cache_model(cache_model);
+        }
+
+        if let Some(ctx) = webview.web_context() {
+            ctx.connect_initialize_notification_permissions(sync_notification_permissions);
+        }
     });
 
     let argv0 = std::env::args().next().unwrap_or_default();
     app.run_with_args(&[argv0]);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uri_host_strips_userinfo_port_and_case() {
+        assert_eq!(uri_host("https://Example.COM/path?q=1").as_deref(), Some("example.com"));
+        assert_eq!(uri_host("http://user:pw@example.com:8080/").as_deref(), Some("example.com"));
+        assert_eq!(uri_host("http://[::1]:3000/x").as_deref(), Some("::1"));
+        assert_eq!(uri_host("https://example.com#top").as_deref(), Some("example.com"));
+        assert_eq!(uri_host("file:///etc/hosts"), None);
+        assert_eq!(uri_host("example.com"), None);
+    }
+
+    #[test]
+    fn local_hosts_are_recognised() {
+        for host in ["localhost", "app.localhost", "127.0.0.1", "10.0.0.5", "192.168.1.1", "169.254.0.1", "::1", "fd00::1", "fe80::1"] {
+            assert!(is_local_host(host), "{host}");
+        }
+        for host in ["example.com", "8.8.8.8", "2001:4860::8888", "localhost.example.com"] {
+            assert!(!is_local_host(host), "{host}");
+        }
+    }
+
+    #[test]
+    fn https_upgrade_respects_mode_local_hosts_and_exceptions() {
+        HTTPS_ONLY.with(|h| *h.borrow_mut() = false);
+        assert_eq!(https_upgrade("http://example.com/", false), None);
+        HTTPS_ONLY.with(|h| *h.borrow_mut() = true);
+        assert_eq!(https_upgrade("http://example.com/a?b=c", false).as_deref(), Some("https://example.com/a?b=c"));
+        assert_eq!(https_upgrade("https://example.com/", false), None);
+        assert_eq!(https_upgrade("http://localhost:8000/", false), None);
+        assert_eq!(https_upgrade("http://192.168.0.1/", false), None);
+        HTTPS_EXCEPTIONS.with(|e| e.borrow_mut().push("legacy.example".to_string()));
+        assert_eq!(https_upgrade("http://legacy.example/", false), None);
+        PRIVATE_HTTPS_EXCEPTIONS.with(|e| e.borrow_mut().push("private.example".to_string()));
+        assert_eq!(https_upgrade("http://private.example/", true), None);
+        assert_eq!(https_upgrade("http://private.example/", false).as_deref(), Some("https://private.example/"));
+    }
+
+    #[test]
+    fn external_scheme_skips_web_schemes() {
+        assert_eq!(external_scheme("mailto:someone@example.com").as_deref(), Some("mailto"));
+        assert_eq!(external_scheme("TEL:+15551234").as_deref(), Some("tel"));
+        assert_eq!(external_scheme("git+ssh://host/repo").as_deref(), Some("git+ssh"));
+        for uri in ["https://example.com", "HTTP://example.com", "about:blank", "data:text/plain,x", "javascript:void(0)", "rug://home"] {
+            assert_eq!(external_scheme(uri), None, "{uri}");
+        }
+        assert_eq!(external_scheme("1password:open"), None);
+        assert_eq!(external_scheme("no scheme here"), None);
+    }
+
+    #[test]
+    fn typed_scheme_tells_schemes_from_host_and_port() {
+        assert_eq!(typed_scheme("mailto:someone@example.com").as_deref(), Some("mailto"));
+        assert_eq!(typed_scheme("magnet:?xt=urn:btih:abc").as_deref(), Some("magnet"));
+        assert_eq!(typed_scheme("localhost:8080"), None);
+        assert_eq!(typed_scheme("example.com:443/path"), None);
+        assert_eq!(typed_scheme("devbox:3000?debug=1"), None);
+        assert_eq!(typed_scheme("https://example.com"), None);
+    }
+
+    #[test]
+    fn fuzzy_score_matches_in_order_ignoring_case_and_spaces() {
+        assert_eq!(fuzzy_score("GitHub", "git"), Some(13));
+        assert_eq!(fuzzy_score("github", "G I T"), Some(13));
+        assert_eq!(fuzzy_score("github", "gh"), Some(4));
+        assert_eq!(fuzzy_score("github", ""), Some(0));
+        assert_eq!(fuzzy_score("github", "tg"), None);
+        assert_eq!(fuzzy_score("github", "xyz"), None);
+        assert_eq!(fuzzy_score("Überblick", "üb"), Some(8));
+    }
+
+    #[test]
+    fn fuzzy_score_prefers_early_contiguous_matches() {
+        let contiguous = fuzzy_score("github", "git").unwrap();
+        let scattered = fuzzy_score("good idea too", "git").unwrap();
+        let late = fuzzy_score("the git book", "git").unwrap();
+        assert!(contiguous > late && late > scattered);
+    }
+
+    fn set_history(urls: &[&str]) {
+        RECENT_PAGES.with(|rp| *rp.borrow_mut() = urls.iter().map(|u| (u.to_string(), String::new(), None)).collect());
+    }
+
+    #[test]
+    fn inline_completion_matches_each_form_up_to_the_host() {
+        set_history(&["https://www.example.com/docs/intro", "http://localhost:8080/app"]);
+        assert_eq!(inline_completion("exa").as_deref(), Some("example.com"));
+        assert_eq!(inline_completion("www.ex").as_deref(), Some("www.example.com"));
+        assert_eq!(inline_completion("https://www.e").as_deref(), Some("https://www.example.com"));
+        assert_eq!(inline_completion("local").as_deref(), Some("localhost:8080"));
+        assert_eq!(inline_completion("example.com"), None);
+        assert_eq!(inline_completion(""), None);
+        assert_eq!(inline_completion("exa mple"), None);
+    }
+
+    #[test]
+    fn inline_completion_completes_the_path_once_a_slash_is_typed() {
+        set_history(&["https://www.example.com/docs/intro"]);
+        assert_eq!(inline_completion("example.com/").as_deref(), Some("example.com/docs/intro"));
+        assert_eq!(inline_completion("https://www.example.com/d").as_deref(), Some("https://www.example.com/docs/intro"));
+        assert_eq!(inline_completion("example.com/docs/intro"), None);
+    }
+
+    #[test]
+    fn inline_completion_keeps_the_typed_case() {
+        set_history(&["https://example.com/"]);
+        assert_eq!(inline_completion("EXA").as_deref(), Some("EXAmple.com"));
+        assert_eq!(inline_completion("HTTPS://Ex").as_deref(), Some("HTTPS://Example.com"));
+    }
+
+    #[test]
+    fn inline_completion_handles_non_ascii_input() {
+        set_history(&["https://bücher.de/liste"]);
+        assert_eq!(inline_completion("bü").as_deref(), Some("bücher.de"));
+        assert_eq!(inline_completion("bx"), None);
+        assert_eq!(inline_completion("BÜ"), None);
+        assert_eq!(inline_completion("ü"), None);
+    }
+
+    fn bindings_for<'a>(bindings: &'a [(String, Vec<String>)], action: &str) -> &'a [String] {
+        &bindings.iter().find(|(a, _)| a == action).unwrap().1
+    }
+
+    #[test]
+    fn keybindings_fall_back_to_defaults() {
+        for content in [None, Some(""), Some("not json"), Some("{}")] {
+            let (bindings, invalid) = parse_keybindings(content, |_| true);
+            assert_eq!(bindings.len(), SHORTCUTS.len());
+            assert_eq!(bindings_for(&bindings, "win.reload"), ["F5", "<Control>r"]);
+            assert!(invalid.is_empty());
+        }
+    }
+
+    #[test]
+    fn keybindings_accept_strings_lists_and_short_names() {
+        let content = r#"{"win.new-tab": "<Control>n", "reload": ["F5", 7, "<Control><Shift>x"], "win.find": []}"#;
+        let (bindings, _) = parse_keybindings(Some(content), |_| true);
+        assert_eq!(bindings_for(&bindings, "win.new-tab"), ["<Control>n"]);
+        assert_eq!(bindings_for(&bindings, "win.reload"), ["F5", "<Control><Shift>x"]);
+        assert!(bindings_for(&bindings, "win.find").is_empty());
+        assert_eq!(bindings_for(&bindings, "win.close-tab"), ["<Control>w"]);
+    }
+
+    #[test]
+    fn keybindings_report_rejected_accelerators() {
+        let content = r#"{"win.back": ["<Alt>Left", "<Bogus>q"]}"#;
+        let (bindings, invalid) = parse_keybindings(Some(content), |a| !a.contains("Bogus"));
+        assert_eq!(bindings_for(&bindings, "win.back"), ["<Alt>Left"]);
+        assert_eq!(invalid, [("Back".to_string(), "<Bogus>q".to_string())]);
+    }
+
+    #[test]
+    fn clean_url_strips_wildcard_and_listed_params() {
+        assert_eq!(clean_url("https://example.com/a?utm_source=x&id=7&utm_medium=y"), "https://example.com/a?id=7");
+        assert_eq!(clean_url("https://example.com/?fbclid=abc&q=rust+lang"), "https://example.com/?q=rust+lang");
+        // Names are compared decoded, values are left as they were
+        assert_eq!(clean_url("https://example.com/?utm%5Fcampaign=1&q=%20"), "https://example.com/?q=%20");
+        assert_eq!(clean_url("https://example.com/?utmost=1"), "https://example.com/?utmost=1");
+    }
+
+    #[test]
+    fn clean_url_applies_site_params_to_that_site_only() {
+        SITE_TRACKING_PARAMS.with(|t| t.borrow_mut().push(("shop.example".to_string(), "ref, tag*".to_string())));
+        assert_eq!(clean_url("https://www.shop.example/item?ref=home&tag_id=3&size=m"), "https://www.shop.example/item?size=m");
+        assert_eq!(clean_url("https://other.example/item?ref=home"), "https://other.example/item?ref=home");
+    }
+
+    #[test]
+    fn clean_url_keeps_fragment_and_drops_empty_query() {
+        assert_eq!(clean_url("https://example.com/doc?utm_source=x#part-2"), "https://example.com/doc#part-2");
+        assert_eq!(clean_url("https://example.com/doc?gclid=1&utm_term=2"), "https://example.com/doc");
+        assert_eq!(clean_url("https://example.com/doc#frag?utm_source=x"), "https://example.com/doc#frag?utm_source=x");
+    }
+
+    #[test]
+    fn clean_url_leaves_urls_without_query_alone() {
+        for uri in ["https://example.com/", "https://example.com/path#top", "about:blank"] {
+            assert_eq!(clean_url(uri), uri);
+        }
+    }
+}
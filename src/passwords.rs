@@ -0,0 +1,406 @@
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Nonce};
+use std::cell::RefCell;
+use std::io::Write;
+use std::os::unix::fs::OpenOptionsExt;
+
+// Injected into the isolated "rug" world so pages can neither see nor spoof the handler
+pub const FORM_SCRIPT: &str = r#"(() => {
+  const handler = window.webkit.messageHandlers.passwords;
+  const visible = el => el.getClientRects().length > 0;
+  const loginFields = root => {
+    const inputs = Array.from(root.querySelectorAll('input'));
+    const password = inputs.find(i => i.type === 'password' && visible(i));
+    if (!password) return null;
+    const users = inputs.slice(0, inputs.indexOf(password))
+      .filter(i => ['text', 'email', 'tel'].includes(i.type) && visible(i));
+    return { username: users.pop() || null, password };
+  };
+  let reported = '';
+  const report = root => {
+    const fields = loginFields(root);
+    if (!fields || !fields.password.value) return;
+    const username = fields.username ? fields.username.value : '';
+    if (reported === username + '\n' + fields.password.value) return;
+    reported = username + '\n' + fields.password.value;
+    handler.postMessage({ type: 'submit', username, password: fields.password.value });
+  };
+  document.addEventListener('submit', e => report(e.target), true);
+  // Script-driven logins never fire submit, so watch their buttons and Enter too
+  document.addEventListener('click', e => {
+    const button = e.target.closest && e.target.closest('button, input[type=submit]');
+    if (button && button.type !== 'button') report(button.form || document);
+  }, true);
+  document.addEventListener('keydown', e => {
+    if (e.key === 'Enter' && e.target.type === 'password') report(e.target.form || document);
+  }, true);
+
+  const setValue = (input, value) => {
+    input.value = value;
+    input.dispatchEvent(new Event('input', { bubbles: true }));
+    input.dispatchEvent(new Event('change', { bubbles: true }));
+  };
+  window.rugFill = logins => {
+    const fields = loginFields(document);
+    if (!fields || !logins.length) return;
+    let login = logins[0];
+    if (fields.username && fields.username.value) {
+      login = logins.find(l => l[0] === fields.username.value);
+      if (!login) return;
+    }
+    if (fields.username && !fields.username.value) setValue(fields.username, login[0]);
+    if (!fields.password.value) setValue(fields.password, login[1]);
+  };
+  const query = () => {
+    if (!loginFields(document)) return false;
+    handler.postMessage({ type: 'query' }).then(reply => window.rugFill(JSON.parse(reply)));
+    return true;
+  };
+  // A locked vault is only opened once the user reaches for a credential field, never on load
+  let unlockAsked = false;
+  const reach = e => {
+    const fields = loginFields(document);
+    if (unlockAsked || !e.isTrusted || !fields || ![fields.username, fields.password].includes(e.target)) return;
+    unlockAsked = true;
+    handler.postMessage({ type: 'unlock' }).then(reply => window.rugFill(JSON.parse(reply)));
+  };
+  document.addEventListener('pointerdown', reach, true);
+  document.addEventListener('keydown', reach, true);
+  // Login forms are often rendered late by scripts
+  if (!query()) {
+    const observer = new MutationObserver(() => { if (query()) observer.disconnect(); });
+    observer.observe(document.documentElement, { childList: true, subtree: true });
+    setTimeout(() => observer.disconnect(), 10000);
+  }
+})();
+"#;
+
+thread_local! {
+    // Set once unlocked; the salt is only kept when the key came from a master password
+    static VAULT_KEY: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+    static VAULT_SALT: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+    static CREDENTIALS: RefCell<Vec<(String, String, String)>> = const { RefCell::new(Vec::new()) };
+}
+
+fn vault_path() -> std::path::PathBuf {
+    #[cfg(debug_assertions)]
+    { std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("target/tmp/passwords.json") }
+    #[cfg(not(debug_assertions))]
+    { glib::home_dir().join(".local/share/rug/passwords.json") }
+}
+
+fn read_vault() -> Option<serde_json::Value> {
+    let content = std::fs::read_to_string(vault_path()).ok()?;
+    serde_json::from_str(&content).ok()
+}
+
+fn decrypt(vault: &serde_json::Value, key: &[u8]) -> Option<Vec<(String, String, String)>> {
+    let nonce = glib::base64_decode(vault["nonce"].as_str()?);
+    let data = glib::base64_decode(vault["data"].as_str()?);
+    if nonce.len() != 12 { return None; }
+    let cipher = Aes256Gcm::new_from_slice(key).ok()?;
+    let plain = cipher.decrypt(Nonce::from_slice(&nonce), data.as_slice()).ok()?;
+    serde_json::from_slice(&plain).ok()
+}
+
+fn encrypt(credentials: &[(String, String, String)], key: &[u8], salt: Option<&[u8]>) -> Option<serde_json::Value> {
+    let cipher = Aes256Gcm::new_from_slice(key).ok()?;
+    let plain = serde_json::to_vec(credentials).ok()?;
+    let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+    let data = cipher.encrypt(&nonce, plain.as_slice()).ok()?;
+    Some(serde_json::json!({
+        "key": if salt.is_some() { "master" } else { "keyring" },
+        "salt": salt.map(|s| glib::base64_encode(s).to_string()),
+        "nonce": glib::base64_encode(&nonce).to_string(),
+        "data": glib::base64_encode(&data).to_string(),
+    }))
+}
+
+fn write_vault() {
+    let Some(key) = VAULT_KEY.with(|k| k.borrow().clone()) else { return };
+    let salt = VAULT_SALT.with(|s| s.borrow().clone());
+    let credentials = CREDENTIALS.with(|c| c.borrow().clone());
+    let Some(vault) = encrypt(&credentials, &key, salt.as_deref()) else { return };
+    let path = vault_path();
+    if let Some(parent) = path.parent() { let _ = std::fs::create_dir_all(parent); }
+    let file = std::fs::OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(&path);
+    if let Ok(mut file) = file {
+        let _ = file.write_all(vault.to_string().as_bytes());
+    }
+}
+
+pub fn vault_exists() -> bool {
+    vault_path().exists()
+}
+
+pub fn is_unlocked() -> bool {
+    VAULT_KEY.with(|k| k.borrow().is_some())
+}
+
+fn set_unlocked(key: Vec<u8>, salt: Option<Vec<u8>>, credentials: Vec<(String, String, String)>) {
+    CREDENTIALS.with(|c| *c.borrow_mut() = credentials);
+    VAULT_SALT.with(|s| *s.borrow_mut() = salt);
+    VAULT_KEY.with(|k| *k.borrow_mut() = Some(key));
+}
+
+// Vaults written before origins were encrypted still list them in the clear
+fn scrub_origins(vault: &serde_json::Value) {
+    if vault.get("origins").is_some() { write_vault(); }
+}
+
+// Opens an existing vault through the system keyring; false means a master password is needed instead
+pub async fn unlock_with_keyring() -> bool {
+    if is_unlocked() { return true; }
+    let Some(vault) = read_vault() else { return false };
+    if vault["key"].as_str() == Some("master") { return false; }
+    let Some(key) = keyring::lookup().await else { return false };
+    let Some(credentials) = decrypt(&vault, &key) else { return false };
+    set_unlocked(key, None, credentials);
+    scrub_origins(&vault);
+    true
+}
+
+// Only on the user's say-so: a new vault whose key lives in the system keyring
+pub async fn create_with_keyring() -> bool {
+    if vault_exists() { return unlock_with_keyring().await; }
+    let key = Aes256Gcm::generate_key(OsRng).to_vec();
+    if !keyring::store(&key).await { return false; }
+    set_unlocked(key, None, Vec::new());
+    write_vault();
+    true
+}
+
+pub fn unlock_with_master_password(password: &str) -> Result<(), &'static str> {
+    if is_unlocked() { return Ok(()); }
+    let vault = read_vault();
+    if vault.as_ref().is_some_and(|v| v["key"].as_str() != Some("master")) {
+        return Err("Saved passwords are locked in the system keyring, which is unavailable");
+    }
+    let salt = match vault.as_ref().and_then(|v| v["salt"].as_str()) {
+        Some(salt) => glib::base64_decode(salt),
+        None => {
+            let mut salt = vec![0u8; 16];
+            OsRng.fill_bytes(&mut salt);
+            salt
+        }
+    };
+    let params = argon2::Params::new(19456, 2, 1, Some(32)).map_err(|_| "Invalid key parameters")?;
+    let kdf = argon2::Argon2::new(argon2::Algorithm::Argon2id, argon2::Version::V0x13, params);
+    let mut key = vec![0u8; 32];
+    kdf.hash_password_into(password.as_bytes(), &salt, &mut key).map_err(|_| "Could not derive a key from that password")?;
+    let credentials = match &vault {
+        Some(vault) => decrypt(vault, &key).ok_or("Wrong master password")?,
+        None => Vec::new(),
+    };
+    set_unlocked(key, Some(salt), credentials);
+    match &vault {
+        Some(vault) => scrub_origins(vault),
+        None => write_vault(),
+    }
+    Ok(())
+}
+
+// Origins are encrypted with everything else, so a locked vault may hold any of them
+pub fn may_have_saved(origin: &str) -> bool {
+    if is_unlocked() {
+        return CREDENTIALS.with(|c| c.borrow().iter().any(|(o, _, _)| o == origin));
+    }
+    vault_exists()
+}
+
+pub fn credentials() -> Vec<(String, String, String)> {
+    CREDENTIALS.with(|c| c.borrow().clone())
+}
+
+pub fn logins_for(origin: &str) -> Vec<(String, String)> {
+    CREDENTIALS.with(|c| {
+        c.borrow().iter().filter(|(o, _, _)| o == origin).map(|(_, u, p)| (u.clone(), p.clone())).collect()
+    })
+}
+
+pub fn save(origin: &str, username: &str, password: &str) {
+    update(origin, username, username, password);
+}
+
+pub fn update(origin: &str, old_username: &str, username: &str, password: &str) {
+    if !is_unlocked() { return; }
+    CREDENTIALS.with(|c| {
+        let mut credentials = c.borrow_mut();
+        credentials.retain(|(o, u, _)| !(o == origin && (u == old_username || u == username)));
+        credentials.push((origin.to_string(), username.to_string(), password.to_string()));
+    });
+    write_vault();
+}
+
+pub fn remove(origin: &str, username: &str) {
+    if !is_unlocked() { return; }
+    CREDENTIALS.with(|c| c.borrow_mut().retain(|(o, u, _)| !(o == origin && u == username)));
+    write_vault();
+}
+
+// Same columns as the Firefox and Chromium exporters so the file imports elsewhere.
+// Never replaces an existing file, so the plain text copy always starts out readable by the user only.
+pub fn export_csv(path: &std::path::Path) -> std::io::Result<usize> {
+    let field = |s: &str| format!("\"{}\"", s.replace('"', "\"\""));
+    let credentials = credentials();
+    let mut csv = String::from("url,username,password\n");
+    for (origin, username, password) in &credentials {
+        csv.push_str(&format!("{},{},{}\n", field(origin), field(username), field(password)));
+    }
+    let mut file = std::fs::OpenOptions::new().write(true).create_new(true).mode(0o600).open(path)?;
+    file.write_all(csv.as_bytes())?;
+    Ok(credentials.len())
+}
+
+// The freedesktop Secret Service, the D-Bus API libsecret wraps, keeps the vault key
+mod keyring {
+    use glib::prelude::*;
+    use glib::variant::ObjectPath;
+    use gtk4::gio;
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+    use std::task::{Poll, Waker};
+    use std::time::Duration;
+
+    const SERVICE: &str = "org.freedesktop.secrets";
+    const SERVICE_PATH: &str = "/org/freedesktop/secrets";
+    const COLLECTION_PATH: &str = "/org/freedesktop/secrets/aliases/default";
+    const CALL_TIMEOUT_MS: i32 = 5000;
+    // Long enough to type a keyring password, short enough that a lost dialog doesn't hang saving
+    const PROMPT_TIMEOUT: Duration = Duration::from_secs(120);
+
+    fn attributes() -> HashMap<String, String> {
+        HashMap::from([
+            ("application".to_string(), "rug".to_string()),
+            ("rug-item".to_string(), "password-vault-key".to_string()),
+        ])
+    }
+
+    async fn call(bus: &gio::DBusConnection, path: &str, interface: &str, method: &str, args: glib::Variant) -> Option<glib::Variant> {
+        let interface = format!("org.freedesktop.Secret.{}", interface);
+        bus.call_future(Some(SERVICE), path, &interface, method, Some(&args), None, gio::DBusCallFlags::NONE, CALL_TIMEOUT_MS).await.ok()
+    }
+
+    async fn connect() -> Option<(gio::DBusConnection, ObjectPath)> {
+        let bus = gio::bus_get_future(gio::BusType::Session).await.ok()?;
+        let reply = call(&bus, SERVICE_PATH, "Service", "OpenSession", ("plain", "".to_variant()).to_variant()).await?;
+        let session = reply.child_value(1).get()?;
+        Some((bus, session))
+    }
+
+    // Waits on the service's own confirmation dialog; false when the user dismisses it or it times out
+    async fn prompt(bus: &gio::DBusConnection, path: &str) -> bool {
+        if path == "/" { return true; }
+        let state: Rc<RefCell<(Option<bool>, Option<Waker>)>> = Rc::default();
+        let completed = state.clone();
+        let id = bus.signal_subscribe(
+            Some(SERVICE), Some("org.freedesktop.Secret.Prompt"), Some("Completed"), Some(path), None,
+            gio::DBusSignalFlags::NONE,
+            move |_, _, _, _, _, params| {
+                let mut state = completed.borrow_mut();
+                state.0 = Some(!params.child_value(0).get::<bool>().unwrap_or(true));
+                if let Some(waker) = state.1.take() { waker.wake(); }
+            },
+        );
+        let done = std::future::poll_fn(|cx| {
+            let mut state = state.borrow_mut();
+            match state.0 {
+                Some(result) => Poll::Ready(result),
+                None => {
+                    state.1 = Some(cx.waker().clone());
+                    Poll::Pending
+                }
+            }
+        });
+        let result = call(bus, path, "Prompt", "Prompt", ("",).to_variant()).await.is_some()
+            && glib::future_with_timeout(PROMPT_TIMEOUT, done).await.unwrap_or(false);
+        bus.signal_unsubscribe(id);
+        result
+    }
+
+    async fn unlock(bus: &gio::DBusConnection, objects: Vec<ObjectPath>) -> bool {
+        let Some(reply) = call(bus, SERVICE_PATH, "Service", "Unlock", (objects,).to_variant()).await else { return false };
+        prompt(bus, reply.child_value(1).str().unwrap_or("/")).await
+    }
+
+    pub async fn lookup() -> Option<Vec<u8>> {
+        let (bus, session) = connect().await?;
+        let reply = call(&bus, SERVICE_PATH, "Service", "SearchItems", (attributes(),).to_variant()).await?;
+        let unlocked: Vec<ObjectPath> = reply.child_value(0).get()?;
+        let locked: Vec<ObjectPath> = reply.child_value(1).get()?;
+        let item = match (unlocked.first(), locked.first()) {
+            (Some(item), _) => item.clone(),
+            (None, Some(item)) if unlock(&bus, vec![item.clone()]).await => item.clone(),
+            _ => return None,
+        };
+        let reply = call(&bus, SERVICE_PATH, "Service", "GetSecrets", (vec![item], session).to_variant()).await?;
+        // a{o(oayays)}: the secret value is the third field of the first entry
+        let entry = reply.child_value(0).iter().next()?;
+        entry.child_value(1).child_value(2).get()
+    }
+
+    pub async fn store(key: &[u8]) -> bool {
+        let Some((bus, session)) = connect().await else { return false };
+        let Ok(collection) = ObjectPath::try_from(COLLECTION_PATH) else { return false };
+        if !unlock(&bus, vec![collection]).await { return false; }
+        let properties = HashMap::from([
+            ("org.freedesktop.Secret.Item.Label".to_string(), "rug saved passwords".to_variant()),
+            ("org.freedesktop.Secret.Item.Attributes".to_string(), attributes().to_variant()),
+        ]);
+        let secret = (session, Vec::<u8>::new(), key.to_vec(), "application/octet-stream".to_string());
+        let Some(reply) = call(&bus, COLLECTION_PATH, "Collection", "CreateItem", (properties, secret, true).to_variant()).await else { return false };
+        prompt(&bus, reply.child_value(1).str().unwrap_or("/")).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Vec<(String, String, String)> {
+        vec![
+            ("https://example.com".to_string(), "alice".to_string(), "hunter2".to_string()),
+            ("https://bücher.example".to_string(), "".to_string(), "pässwörd \"quoted\"".to_string()),
+        ]
+    }
+
+    #[test]
+    fn vault_round_trips() {
+        let key = Aes256Gcm::generate_key(OsRng).to_vec();
+        let vault = encrypt(&sample(), &key, None).unwrap();
+        assert_eq!(vault["key"], "keyring");
+        assert!(vault["salt"].is_null());
+        assert!(!vault.to_string().contains("hunter2"));
+        assert!(!vault.to_string().contains("example.com"));
+        assert_eq!(decrypt(&vault, &key), Some(sample()));
+    }
+
+    #[test]
+    fn master_password_vault_keeps_its_salt() {
+        let key = Aes256Gcm::generate_key(OsRng).to_vec();
+        let vault = encrypt(&sample(), &key, Some(&[7u8; 16])).unwrap();
+        assert_eq!(vault["key"], "master");
+        assert_eq!(glib::base64_decode(vault["salt"].as_str().unwrap()), vec![7u8; 16]);
+        assert_eq!(decrypt(&vault, &key), Some(sample()));
+    }
+
+    #[test]
+    fn vault_rejects_wrong_key_and_tampering() {
+        let key = Aes256Gcm::generate_key(OsRng).to_vec();
+        let vault = encrypt(&sample(), &key, None).unwrap();
+        let other = Aes256Gcm::generate_key(OsRng).to_vec();
+        assert_eq!(decrypt(&vault, &other), None);
+
+        let mut data = glib::base64_decode(vault["data"].as_str().unwrap());
+        data[0] ^= 1;
+        let mut tampered = vault.clone();
+        tampered["data"] = serde_json::json!(glib::base64_encode(&data).to_string());
+        assert_eq!(decrypt(&tampered, &key), None);
+
+        let mut short_nonce = vault.clone();
+        short_nonce["nonce"] = serde_json::json!(glib::base64_encode(&[0u8; 8]).to_string());
+        assert_eq!(decrypt(&short_nonce, &key), None);
+    }
+}